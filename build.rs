@@ -0,0 +1,303 @@
+//! Generates the opcode lookup tables from `src/opcodes.tbl`. See the top of that file for the
+//! format of the table.
+use std::{env, fmt::Write, fs, path::Path};
+
+const TABLE_PATH: &str = "src/opcodes.tbl";
+
+// Names of the opcode maps, in the order of the `OpcodeMap` variants
+const MAPS: [(&str, &str); 10] = [
+    ("1b", "OneByte"),
+    ("0f", "Map0F"),
+    ("0f38", "Map0F38"),
+    ("0f3a", "Map0F3A"),
+    ("vex.0f", "Vex0F"),
+    ("vex.0f38", "Vex0F38"),
+    ("vex.0f3a", "Vex0F3A"),
+    ("evex.0f", "Evex0F"),
+    ("evex.0f38", "Evex0F38"),
+    ("evex.0f3a", "Evex0F3A"),
+];
+
+// Conditions encoded in the lower 4 bits of the opcode of the conditional instructions, with the
+// `Condition` variant and the spelling used by the Intel manual
+const CONDITIONS: [(&str, &str); 16] = [
+    ("O", "o"),
+    ("No", "no"),
+    ("B", "b"),
+    ("Ae", "ae"),
+    ("E", "e"),
+    ("Ne", "ne"),
+    ("Be", "be"),
+    ("A", "a"),
+    ("S", "s"),
+    ("Ns", "ns"),
+    ("P", "p"),
+    ("Np", "np"),
+    ("L", "l"),
+    ("Ge", "ge"),
+    ("Le", "le"),
+    ("G", "g"),
+];
+
+// Mandatory prefixes, in the order of the `MandatoryPrefix` variants
+const PREFIXES: [(&str, &str); 4] = [("-", "None"), ("66", "P66"), ("f2", "PF2"), ("f3", "PF3")];
+
+struct Entry {
+    map: usize,
+    opcode: u8,
+    prefix: usize,
+    modrm: (u8, u8),
+    code: String,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={TABLE_PATH}");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let table = fs::read_to_string(TABLE_PATH).expect("Cannot read the opcode table");
+
+    let mut entries = vec![];
+    for (idx, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let row: Vec<&str> = line.split_whitespace().collect();
+        match parse_row(&row) {
+            Ok(mut row_entries) => entries.append(&mut row_entries),
+            Err(err) => panic!("{TABLE_PATH}:{}: {err}", idx + 1),
+        }
+    }
+
+    entries.sort_by_key(|entry| (entry.map, entry.opcode, entry.prefix, entry.modrm));
+
+    let mut index = vec![[(0u16, 0u16); 256]; MAPS.len()];
+    for (pos, entry) in entries.iter().enumerate() {
+        let (start, count) = &mut index[entry.map][entry.opcode as usize];
+        if *count == 0 {
+            *start = pos as u16;
+        }
+        *count += 1;
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "static OPCODE_TABLE: [OpcodeEntry; {}] = [",
+        entries.len()
+    )
+    .unwrap();
+    for entry in entries.iter() {
+        writeln!(out, "    {},", entry.code).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+    writeln!(
+        out,
+        "static OPCODE_INDEX: [[(u16, u16); 256]; {}] = [",
+        MAPS.len()
+    )
+    .unwrap();
+    for map in index.iter() {
+        writeln!(out, "    [").unwrap();
+        for (start, count) in map.iter() {
+            writeln!(out, "        ({start}, {count}),").unwrap();
+        }
+        writeln!(out, "    ],").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    fs::write(Path::new(&out_dir).join("opcode_table.rs"), out)
+        .expect("Cannot write the generated opcode table");
+}
+
+// Parses one row of the table, which gives an entry for each opcode in its opcode range
+fn parse_row(row: &[&str]) -> Result<Vec<Entry>, String> {
+    let [map, opcode, prefix, modrm, mnemonic, ident, operands, implicit, encoding, modes, ext] =
+        row
+    else {
+        return Err(format!("expected 11 columns, found {}", row.len()));
+    };
+
+    let map = MAPS
+        .iter()
+        .position(|(name, _)| name == map)
+        .ok_or_else(|| format!("unknown opcode map `{map}`"))?;
+
+    let (first, last) = match opcode.split_once('-') {
+        Some((first, last)) => (parse_hex(first)?, parse_hex(last)?),
+        None => (parse_hex(opcode)?, parse_hex(opcode)?),
+    };
+    if first > last {
+        return Err(format!("empty opcode range `{opcode}`"));
+    }
+
+    let prefix = PREFIXES
+        .iter()
+        .position(|(name, _)| name == prefix)
+        .ok_or_else(|| format!("unknown mandatory prefix `{prefix}`"))?;
+
+    // The selector is also sorted on, such that the entries of a group are in ModRM.reg order
+    let (modrm, modrm_code) = match *modrm {
+        "-" => ((0, 0), "ModRMSelector::None".to_string()),
+        _ => match (modrm.strip_prefix('/'), modrm.strip_prefix('=')) {
+            (Some(reg), _) => {
+                let reg: u8 = reg
+                    .parse()
+                    .ok()
+                    .filter(|reg| *reg < 8)
+                    .ok_or_else(|| format!("invalid ModRM.reg extension `{modrm}`"))?;
+                ((1, reg), format!("ModRMSelector::Reg({reg})"))
+            }
+            (_, Some(byte)) => {
+                let byte = parse_hex(byte)?;
+                ((2, byte), format!("ModRMSelector::Byte(0x{byte:02x})"))
+            }
+            _ => return Err(format!("invalid ModRM selector `{modrm}`")),
+        },
+    };
+
+    let operand_codes = parse_operand_list(operands)?;
+    let implicit_codes = parse_operand_list(implicit)?;
+
+    let modes = match *modes {
+        "all" => "All",
+        "i64" => "Invalid64",
+        "o64" => "Only64",
+        _ => return Err(format!("unknown modes `{modes}`")),
+    };
+
+    let ext = match *ext {
+        "-" => "None".to_string(),
+        _ => format!("Some(Extension::{ext})"),
+    };
+
+    // Conditional instructions get their condition from the lower 4 bits of the opcode
+    let conditional = ident.ends_with("cc");
+    if conditional != mnemonic.ends_with("cc") {
+        return Err(format!(
+            "`{mnemonic}` and `{ident}` have to both end with `cc`"
+        ));
+    }
+
+    let entries = (first..=last)
+        .map(|opcode| {
+            let (mnemonic, ident) = match conditional {
+                true => {
+                    let (variant, spelling) = CONDITIONS[usize::from(opcode & 0xf)];
+                    (
+                        format!("{}{spelling}", mnemonic.trim_end_matches("cc")),
+                        format!("{ident}(Condition::{variant})"),
+                    )
+                }
+                false => (mnemonic.to_string(), ident.to_string()),
+            };
+            (opcode, mnemonic, ident)
+        })
+        .map(|(opcode, mnemonic, ident)| Entry {
+            map,
+            opcode,
+            prefix,
+            modrm,
+            code: format!(
+                "OpcodeEntry {{ map: OpcodeMap::{}, opcode: 0x{opcode:02x}, \
+                 prefix: MandatoryPrefix::{}, modrm: {modrm_code}, mnemonic: \"{mnemonic}\", \
+                 ident: OpcodeType::{ident}, operands: [{operand_codes}], \
+                 implicit: [{implicit_codes}], encoding: OperandEncoding::{encoding}, \
+                 modes: Modes::{modes}, extension: {ext} }}",
+                MAPS[map].1, PREFIXES[prefix].1,
+            ),
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+// Parses a list of at most 4 operands, each followed by the way it is accessed, into the code of
+// an array of `(OperandSpec, Access)`
+fn parse_operand_list(operands: &str) -> Result<String, String> {
+    let mut codes = vec![];
+    if operands != "-" {
+        for operand in operands.split(',') {
+            let (operand, access) = operand
+                .split_once(':')
+                .ok_or_else(|| format!("missing access for operand `{operand}`"))?;
+            let access = match access {
+                "r" => "Read",
+                "w" => "Write",
+                "rw" => "ReadWrite",
+                "c" => "Conditional",
+                _ => return Err(format!("unknown access `{access}`")),
+            };
+            codes.push(format!(
+                "Some(({}, Access::{access}))",
+                parse_operand(operand)?
+            ));
+        }
+    }
+    if codes.len() > 4 {
+        return Err(format!("more than 4 operands in `{operands}`"));
+    }
+    codes.resize(4, "None".to_string());
+
+    Ok(codes.join(", "))
+}
+
+fn parse_hex(value: &str) -> Result<u8, String> {
+    u8::from_str_radix(value, 16).map_err(|_| format!("invalid hex byte `{value}`"))
+}
+
+// Parses an operand code, into the code of the `OperandSpec` it represents
+fn parse_operand(operand: &str) -> Result<String, String> {
+    let family = match operand {
+        "rAX" => Some("Accumulator"),
+        "rCX" => Some("Counter"),
+        "rDX" => Some("Data"),
+        "rBX" => Some("Base"),
+        "rSP" => Some("StackPointer"),
+        "rBP" => Some("BasePointer"),
+        "rSI" => Some("Source"),
+        "rDI" => Some("Destination"),
+        _ => None,
+    };
+    if let Some(family) = family {
+        return Ok(format!("OperandSpec::RegFamily(RegFamily::{family})"));
+    }
+
+    match operand {
+        "ES" | "CS" | "SS" | "DS" | "FS" | "GS" => {
+            return Ok(format!("OperandSpec::Segment(SegmentRegister::{operand})"))
+        }
+        // A memory operand of any size
+        "M" => return Ok("OperandSpec::Map(AddressingMethod::M, OperandType::V)".to_string()),
+        "Zv" => return Ok("OperandSpec::RegInOpcode".to_string()),
+        _ => {}
+    }
+
+    // Fixed registers are written in upper case, while operand codes start with an upper case
+    // addressing method, followed by a lower case operand type
+    if operand.len() > 1
+        && operand
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Ok(format!("OperandSpec::Reg(Reg::{operand})"));
+    }
+
+    let mut chars = operand.chars();
+    let (Some(addr_meth), Some(op_type), None) = (chars.next(), chars.next(), chars.next()) else {
+        return Err(format!("invalid operand `{operand}`"));
+    };
+    if !"EGIJMOXY".contains(addr_meth) || !"bdpvwz".contains(op_type) {
+        return Err(format!("unsupported operand `{operand}`"));
+    }
+    // Far pointers are only supported in memory
+    if op_type == 'p' && addr_meth != 'M' {
+        return Err(format!("unsupported operand `{operand}`"));
+    }
+
+    Ok(format!(
+        "OperandSpec::Map(AddressingMethod::{addr_meth}, OperandType::{})",
+        op_type.to_ascii_uppercase()
+    ))
+}
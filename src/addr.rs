@@ -0,0 +1,159 @@
+//! Module that computes the addresses memory operands point to, following the addressing rules
+//! described in `explained.md`, such that debuggers and emulators do not have to implement them
+use crate::{
+    arch::Arch,
+    inst::{Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::AddrSize,
+    prefix::Prefix,
+    reg::{Reg, RegClass, RegFamily, SegmentRegister},
+};
+
+/// Provides the values of the registers of the processor, to compute the addresses of memory
+/// operands. Any function which returns the value of a register is a provider with all the segment
+/// bases set to 0.
+pub trait RegisterValues {
+    /// Returns the value of the full-width register `reg`, which is the `parent` of any register
+    /// used to address memory. The instruction pointer is never requested, since it is computed
+    /// from the address of the instruction.
+    fn value(&self, reg: Reg) -> u64;
+
+    /// Returns the base address of the segment `seg`. In real mode, this is the selector
+    /// multiplied by 16. Defaults to 0, as for every segment of a flat memory model.
+    fn segment_base(&self, _seg: SegmentRegister) -> u64 {
+        0
+    }
+}
+
+impl<F: Fn(Reg) -> u64> RegisterValues for F {
+    fn value(&self, reg: Reg) -> u64 {
+        self(reg)
+    }
+}
+
+impl Instruction {
+    /// Returns the segment register the memory `operand` is accessed through, or `None` if the
+    /// operand is not in memory. This is the segment of the override prefix, if the instruction
+    /// has one, or SS for addresses based on rSP or rBP and DS for all the others. The destination
+    /// of string instructions is always in ES.
+    pub fn segment(&self, operand: &ResolvedOperand) -> Option<SegmentRegister> {
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, _)) = operand else {
+            return None;
+        };
+        let base = match eff_addr {
+            EffAddrType::Reg(reg) => Some(*reg),
+            _ => maybe_sib.and_then(|sib| sib.base()),
+        };
+        let family = base.and_then(|reg| reg.family());
+
+        if self.opcode.ident.is_string() && family == Some(RegFamily::Destination) {
+            return Some(SegmentRegister::ES);
+        }
+
+        // The last segment override prefix wins
+        let seg_override = self
+            .prefixes()
+            .iter()
+            .rev()
+            .find_map(|prefix| match prefix {
+                Prefix::Group2(group) => Some(group.segment()),
+                _ => None,
+            });
+
+        match (seg_override, family) {
+            (Some(seg), _) => Some(seg),
+            (None, Some(RegFamily::StackPointer | RegFamily::BasePointer)) => {
+                Some(SegmentRegister::SS)
+            }
+            (None, _) => Some(SegmentRegister::DS),
+        }
+    }
+
+    /// Returns the effective address of the memory `operand`, which is its offset in the segment:
+    /// the base, plus the index multiplied by the scale, plus the displacement, wrapped around at
+    /// the address size. `address` is the address of the instruction, from which rip-relative
+    /// operands are computed. Returns `None` if the operand is not in memory.
+    pub fn effective_address(
+        &self,
+        operand: &ResolvedOperand,
+        address: u64,
+        regs: &impl RegisterValues,
+    ) -> Option<u64> {
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) = operand else {
+            return None;
+        };
+
+        let read = |reg: Reg| match reg.class() {
+            // The instruction pointer already points to the next instruction
+            RegClass::InstructionPointer => address.wrapping_add(self.layout().len() as u64),
+            _ => (regs.value(reg.parent()) >> reg.bit_offset()) & mask(reg.width()),
+        };
+
+        // Displacements are sign-extended to the address size
+        let mut eff_addr_value = maybe_disp.map_or(0, |disp| disp.value() as u64);
+        if let EffAddrType::Reg(reg) = eff_addr {
+            eff_addr_value = eff_addr_value.wrapping_add(read(*reg));
+        }
+        if let Some(sib) = maybe_sib {
+            if let Some(base) = sib.base() {
+                eff_addr_value = eff_addr_value.wrapping_add(read(base));
+            }
+            if let Some(index) = sib.index() {
+                let scaled_index = read(index).wrapping_mul(u64::from(sib.scale()));
+                eff_addr_value = eff_addr_value.wrapping_add(scaled_index);
+            }
+        }
+
+        let addr_bits = match self.address_size() {
+            AddrSize::Addr16Bit => 16,
+            AddrSize::Addr32Bit => 32,
+            AddrSize::Addr64Bit => 64,
+        };
+        Some(eff_addr_value & mask(addr_bits))
+    }
+
+    /// Returns the address `operand` refers to relative to the instruction pointer, which is the
+    /// target of a relative branch or the address of a rip-relative memory operand. `address` is
+    /// the address of the instruction. Returns `None` for any other operand.
+    pub fn target(&self, operand: &ResolvedOperand, address: u64) -> Option<u64> {
+        match operand {
+            ResolvedOperand::Relative(_) => self.branch_target(address),
+            ResolvedOperand::Mem((EffAddrType::Reg(reg), _, _))
+                if reg.class() == RegClass::InstructionPointer =>
+            {
+                // No other register is part of a rip-relative address
+                self.effective_address(operand, address, &|_| 0)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the linear address of the memory `operand`, which is its effective address added to
+    /// the base of its segment. In 64-bit mode, only the FS and GS segments have a base. Otherwise,
+    /// the linear address wraps around at 32 bits. Returns `None` if the operand is not in memory.
+    pub fn linear_address(
+        &self,
+        operand: &ResolvedOperand,
+        address: u64,
+        regs: &impl RegisterValues,
+    ) -> Option<u64> {
+        let eff_addr = self.effective_address(operand, address, regs)?;
+        let seg = self.segment(operand)?;
+
+        match (self.cpu_mode(), seg) {
+            (Arch::Arch64, SegmentRegister::FS | SegmentRegister::GS) => {
+                Some(regs.segment_base(seg).wrapping_add(eff_addr))
+            }
+            (Arch::Arch64, _) => Some(eff_addr),
+            (_, _) => Some(regs.segment_base(seg).wrapping_add(eff_addr) & mask(32)),
+        }
+    }
+}
+
+// Returns the mask of the lower `bits` bits
+fn mask(bits: u16) -> u64 {
+    match bits {
+        0..=63 => (1 << bits) - 1,
+        _ => u64::MAX,
+    }
+}
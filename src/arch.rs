@@ -1 +1,10 @@
 //! Describes the supported architectures for Intel x86_64 processor
+
+/// The width of the code being decoded. It selects the default operand and address sizes, the
+/// addressing forms of the ModRM byte and which registers can be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    Arch16,
+    Arch32,
+    Arch64,
+}
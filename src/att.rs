@@ -0,0 +1,295 @@
+//! Module that displays instructions in the AT&T syntax, used by default by the GNU assembler and
+//! objdump: the source operand comes first, registers are prefixed by `%`, immediates by `$`,
+//! memory operands are written `disp(base,index,scale)` and the mnemonic carries the operand size
+//! when no register operand implies it.
+use crate::{
+    format::{write_prefixes, FormatOptions, Formatter},
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::{OpSize, OpcodeType},
+    prefix::Prefix,
+    reg::Reg,
+    source::is_rip_relative,
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt;
+
+/// Formats instructions in the AT&T syntax, like `mov    %rsp,%rbp`. The size of memory operands
+/// is given by a mnemonic suffix instead of a keyword, so the `memory_size` option is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct AttFormatter {
+    options: FormatOptions,
+}
+
+impl AttFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    // Returns the mnemonic of the instruction, without its size suffix
+    fn mnemonic(inst: &Instruction) -> &'static str {
+        match inst.opcode.ident {
+            OpcodeType::CallFar => "lcall",
+            OpcodeType::JmpFar => "ljmp",
+            // The sizes of both operands are part of the mnemonic
+            OpcodeType::Movzx => "movz",
+            OpcodeType::Movsx => "movs",
+            _ => inst.opcode.mnemonic,
+        }
+    }
+
+    // Returns the suffixes which give the size of the operands, if the operands do not imply it
+    fn suffixes(inst: &Instruction) -> [Option<char>; 2] {
+        let mut explicit = inst.operands.explicit();
+        match inst.opcode.ident {
+            OpcodeType::Movzx | OpcodeType::Movsx => {
+                let dest = explicit.next().and_then(|op| suffix(op.size));
+                let src = explicit.next().and_then(|op| suffix(op.size));
+                [src, dest]
+            }
+            // The size of these operands is fixed by the mode or by the instruction
+            OpcodeType::Push
+            | OpcodeType::Pop
+            | OpcodeType::CallNear
+            | OpcodeType::CallFar
+            | OpcodeType::JmpNear
+            | OpcodeType::JmpFar
+            | OpcodeType::Setcc(_) => [None, None],
+            _ => {
+                let mut mem_size = None;
+                for operand in explicit {
+                    match operand.operand {
+                        ResolvedOperand::Reg(_) | ResolvedOperand::Segment(_) => {
+                            return [None, None]
+                        }
+                        ResolvedOperand::Mem(_) => mem_size = Some(operand.size),
+                        _ => {}
+                    }
+                }
+                [mem_size.and_then(suffix), None]
+            }
+        }
+    }
+
+    // Writes the memory `operand`, preceded by its segment if the instruction overrides it
+    fn write_mem(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &ResolvedOperand,
+    ) -> fmt::Result {
+        let options = &self.options;
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) = operand else {
+            return Ok(());
+        };
+
+        // String instructions always show their segments, since only one of them can be overridden
+        let has_override = inst
+            .prefixes()
+            .iter()
+            .any(|prefix| matches!(prefix, Prefix::Group2(_)));
+        if has_override || inst.opcode.ident.is_string() {
+            if let Some(seg) = inst.segment(operand) {
+                out.write_token(TokenKind::Register, "%")?;
+                options.write_name(out, TokenKind::Register, &seg.to_string())?;
+                write!(out, ":")?;
+            }
+        }
+
+        let (base, index) = match eff_addr {
+            EffAddrType::Reg(reg) => (Some(*reg), None),
+            _ => (
+                maybe_sib.and_then(|sib| sib.base()),
+                maybe_sib.and_then(|sib| sib.index()),
+            ),
+        };
+
+        // A displacement added to a register is signed, while a displacement alone is an address
+        match (maybe_disp, base.is_some() || index.is_some()) {
+            (Some(disp), true) => {
+                options.write_signed_hex(out, TokenKind::Displacement, disp.value())?
+            }
+            (Some(disp), false) => {
+                options.write_hex(out, TokenKind::Displacement, disp.unsigned())?
+            }
+            (None, _) => {}
+        }
+
+        if base.is_none() && index.is_none() {
+            return Ok(());
+        }
+        out.write_token(TokenKind::MemoryBracket, "(")?;
+        if let Some(base) = base {
+            self.write_reg(out, base)?;
+        }
+        if let Some(index) = index {
+            write!(out, ",")?;
+            self.write_reg(out, index)?;
+            // The scale is always written, even if it is 1
+            let scale = maybe_sib.map_or(1, |sib| sib.scale());
+            write!(out, ",")?;
+            out.write_token(TokenKind::Immediate, &scale.to_string())?;
+        }
+        out.write_token(TokenKind::MemoryBracket, ")")
+    }
+
+    // Returns whether the operands of `inst` are targets of indirect branches, which are prefixed
+    // by `*`
+    fn is_indirect(inst: &Instruction) -> bool {
+        matches!(
+            inst.opcode.ident,
+            OpcodeType::CallNear | OpcodeType::CallFar | OpcodeType::JmpNear | OpcodeType::JmpFar
+        )
+    }
+
+    // Writes the register `reg`, prefixed by `%`
+    fn write_reg(&self, out: &mut dyn FormatterOutput, reg: Reg) -> fmt::Result {
+        out.write_token(TokenKind::Register, "%")?;
+        self.options.write_reg(out, reg)
+    }
+}
+
+// GNU objdump writes no space after the commas that separate the operands
+impl Default for AttFormatter {
+    fn default() -> Self {
+        Self::new(FormatOptions::default().with_space_after_comma(false))
+    }
+}
+
+impl Formatter for AttFormatter {
+    fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        write_prefixes(out, &self.options, inst)?;
+        let mut mnemonic = String::from(Self::mnemonic(inst));
+        mnemonic.extend(Self::suffixes(inst).iter().flatten());
+        self.options.write_name(out, TokenKind::Mnemonic, &mnemonic)
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        let indirect = Self::is_indirect(inst);
+        match operand.operand {
+            ResolvedOperand::Reg(reg) => {
+                if indirect {
+                    write!(out, "*")?;
+                }
+                self.write_reg(out, reg)
+            }
+            ResolvedOperand::Mem(_) => {
+                if indirect {
+                    write!(out, "*")?;
+                }
+                self.write_mem(out, inst, &operand.operand)
+            }
+            ResolvedOperand::Segment(seg) => {
+                out.write_token(TokenKind::Register, "%")?;
+                self.options
+                    .write_name(out, TokenKind::Register, &seg.to_string())
+            }
+            ResolvedOperand::Immediate(imm) => {
+                out.write_token(TokenKind::Immediate, "$")?;
+                match self.options.signed_immediates() {
+                    true => {
+                        let value = imm.signed_value();
+                        self.options
+                            .write_signed_hex(out, TokenKind::Immediate, value)
+                    }
+                    false => self
+                        .options
+                        .write_hex(out, TokenKind::Immediate, imm.value()),
+                }
+            }
+            ResolvedOperand::Relative(rel) => {
+                if rel.signed_value() >= 0 {
+                    out.write_token(TokenKind::Immediate, "+")?;
+                }
+                self.options
+                    .write_signed_hex(out, TokenKind::Immediate, rel.signed_value())
+            }
+            ResolvedOperand::ToBeDecided => write!(out, "UNKNOWN"),
+        }
+    }
+
+    // Immediates keep their `$` and rip-relative operands their register, like `<main+0x10>(%rip)`
+    fn write_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        name: &str,
+    ) -> fmt::Result {
+        match operand.operand {
+            ResolvedOperand::Immediate(_) => out.write_token(TokenKind::Immediate, "$")?,
+            ResolvedOperand::Mem(_) if Self::is_indirect(inst) => write!(out, "*")?,
+            _ => {}
+        }
+        out.write_token(TokenKind::Symbol, &format!("<{name}>"))?;
+        match operand.operand {
+            ResolvedOperand::Mem((EffAddrType::Reg(reg), _, _))
+                if is_rip_relative(&operand.operand) =>
+            {
+                out.write_token(TokenKind::MemoryBracket, "(")?;
+                self.write_reg(out, reg)?;
+                out.write_token(TokenKind::MemoryBracket, ")")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Comments start with `#` for the GNU assembler
+    fn write_comment(&self, out: &mut dyn FormatterOutput, comment: &str) -> fmt::Result {
+        write!(out, "  ")?;
+        out.write_token(TokenKind::Comment, &format!("# {comment}"))
+    }
+
+    // The destination is the last operand. The immediates of `enter` are not reversed, since
+    // neither of them is a destination.
+    fn operands<'a>(&self, inst: &'a Instruction) -> Vec<&'a InstOperand> {
+        let mut operands: Vec<_> = inst.operands.explicit().collect();
+        if inst.opcode.ident != OpcodeType::Enter {
+            operands.reverse();
+        }
+        operands
+    }
+
+    // Like objdump, the operands are aligned after the mnemonic
+    fn mnemonic_width(&self) -> usize {
+        6
+    }
+}
+
+/// An instruction displayed in the AT&T syntax, with the default options
+pub struct Att<'a> {
+    inst: &'a Instruction,
+}
+
+impl Instruction {
+    /// Returns the instruction, displayed in the AT&T syntax
+    pub fn att(&self) -> Att<'_> {
+        Att { inst: self }
+    }
+}
+
+impl fmt::Display for Att<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        AttFormatter::default().write(f, self.inst)
+    }
+}
+
+// Returns the mnemonic suffix of an operand of size `size`
+fn suffix(size: OpSize) -> Option<char> {
+    match size {
+        OpSize::U8 | OpSize::I8 => Some('b'),
+        OpSize::U16 | OpSize::I16 => Some('w'),
+        OpSize::U32 | OpSize::I32 => Some('l'),
+        OpSize::U64 | OpSize::I64 => Some('q'),
+        OpSize::CpuMode => None,
+    }
+}
@@ -0,0 +1,175 @@
+//! Module that classifies instructions, such that analysis passes do not have to match on their
+//! mnemonics
+use crate::{cpu::Extension, opcode::OpcodeType};
+use core::fmt;
+
+/// The kind of operation an instruction performs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    // Integer arithmetic, like `add` or `mul`
+    Arithmetic,
+    // Bitwise operations, like `and` or `not`
+    Logic,
+    // BCD and ASCII adjustments, like `daa`
+    Decimal,
+    // Moves data between registers and memory, like `mov` or `movzx`
+    DataMove,
+    // Pushes to or pops from the stack
+    Stack,
+    // String instructions, like `movs` or `stos`
+    String,
+    // Branches taken depending on RFLAGS, like `jz`
+    BranchConditional,
+    // Branches that are always taken, like `jmp`
+    BranchUnconditional,
+    Call,
+    Return,
+    // Instructions that manage the processor state, like `hlt` or `syscall`
+    System,
+    // Control-flow Enforcement Technology markers, like `endbr64`
+    Cet,
+    // Operations on vector registers
+    Simd,
+    // Computes an address without accessing memory, like `lea`
+    Misc,
+    Nop,
+    // Prefix bytes, which are not instructions by themselves
+    Prefix,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Arithmetic => "arithmetic",
+            Self::Logic => "logic",
+            Self::Decimal => "decimal",
+            Self::DataMove => "data-move",
+            Self::Stack => "stack",
+            Self::String => "string",
+            Self::BranchConditional => "branch-conditional",
+            Self::BranchUnconditional => "branch-unconditional",
+            Self::Call => "call",
+            Self::Return => "return",
+            Self::System => "system",
+            Self::Cet => "cet",
+            Self::Simd => "simd",
+            Self::Misc => "misc",
+            Self::Nop => "nop",
+            Self::Prefix => "prefix",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The privilege level code has to run at, to execute an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privilege {
+    // Any privilege level, including user mode
+    Ring3,
+    // Only the kernel
+    Ring0,
+}
+
+/// The classification of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub category: Category,
+    /// The CPUID feature the instruction requires, or `None` if it is part of the base instruction
+    /// set
+    pub extension: Option<Extension>,
+    pub privilege: Privilege,
+    /// Whether at least one form of the instruction is valid in 64-bit mode. The opcode table
+    /// tells which forms are
+    pub valid_in_64: bool,
+}
+
+impl OpcodeInfo {
+    const fn base(category: Category) -> Self {
+        Self {
+            category,
+            extension: None,
+            privilege: Privilege::Ring3,
+            valid_in_64: true,
+        }
+    }
+
+    /// Returns whether the instruction may transfer control somewhere else than the next
+    /// instruction
+    pub fn is_branch(&self) -> bool {
+        matches!(
+            self.category,
+            Category::BranchConditional
+                | Category::BranchUnconditional
+                | Category::Call
+                | Category::Return
+        )
+    }
+}
+
+impl OpcodeType {
+    /// Returns the category, the required extension, the privilege level and the 64-bit validity
+    /// of the instruction
+    pub fn info(&self) -> OpcodeInfo {
+        match self {
+            Self::Prefix(_) | Self::Rex(_) | Self::NeedsModRMExtension(_) => {
+                OpcodeInfo::base(Category::Prefix)
+            }
+            Self::Add
+            | Self::Adc
+            | Self::Sub
+            | Self::Sbb
+            | Self::Cmp
+            | Self::Inc
+            | Self::Dec
+            | Self::Neg
+            | Self::Mul
+            | Self::Imul
+            | Self::Div
+            | Self::Idiv => OpcodeInfo::base(Category::Arithmetic),
+            Self::And | Self::Or | Self::Xor | Self::Not | Self::Test => {
+                OpcodeInfo::base(Category::Logic)
+            }
+            Self::Daa | Self::Aaa => OpcodeInfo {
+                valid_in_64: false,
+                ..OpcodeInfo::base(Category::Decimal)
+            },
+            Self::Mov | Self::Movzx | Self::Movsx | Self::Xchg | Self::Setcc(_) => {
+                OpcodeInfo::base(Category::DataMove)
+            }
+            Self::Cmovcc(_) => OpcodeInfo {
+                extension: Some(Extension::Cmov),
+                ..OpcodeInfo::base(Category::DataMove)
+            },
+            Self::Push | Self::Pop | Self::Enter => OpcodeInfo::base(Category::Stack),
+            Self::Movs | Self::Cmps | Self::Stos | Self::Lods | Self::Scas => {
+                OpcodeInfo::base(Category::String)
+            }
+            Self::JmpNear | Self::JmpFar => OpcodeInfo::base(Category::BranchUnconditional),
+            Self::Jcc(_) => OpcodeInfo::base(Category::BranchConditional),
+            Self::CallNear | Self::CallFar => OpcodeInfo::base(Category::Call),
+            Self::RetNear => OpcodeInfo::base(Category::Return),
+            Self::Lea => OpcodeInfo::base(Category::Misc),
+            Self::Nop => OpcodeInfo::base(Category::Nop),
+            Self::Syscall => OpcodeInfo {
+                extension: Some(Extension::Syscall),
+                ..OpcodeInfo::base(Category::System)
+            },
+            Self::Sysret => OpcodeInfo {
+                extension: Some(Extension::Syscall),
+                privilege: Privilege::Ring0,
+                ..OpcodeInfo::base(Category::System)
+            },
+            // `cli` and `sti` also run in user mode if IOPL allows it, which only the kernel sets
+            Self::Hlt | Self::Cli | Self::Sti | Self::Clts | Self::Invd | Self::Wbinvd => {
+                OpcodeInfo {
+                    privilege: Privilege::Ring0,
+                    ..OpcodeInfo::base(Category::System)
+                }
+            }
+            Self::EndBr32 | Self::EndBr64 => OpcodeInfo {
+                extension: Some(Extension::CetIbt),
+                ..OpcodeInfo::base(Category::Cet)
+            },
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Module that describes the condition codes of the conditional instructions, `Jcc`, `SETcc` and
+//! `CMOVcc`, which encode their condition in the lower 4 bits of the opcode
+use crate::{flags::Flags, opcode::OpcodeType};
+use core::fmt;
+
+/// A condition on the RFLAGS register. The variants are in the order of their encoding, such that
+/// a condition and its inverse only differ in the lowest bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Condition {
+    // Overflow(OF = 1)
+    O,
+    // Not overflow(OF = 0)
+    No,
+    // Below, or carry(CF = 1)
+    B,
+    // Above or equal, or not carry(CF = 0)
+    Ae,
+    // Equal, or zero(ZF = 1)
+    E,
+    // Not equal, or not zero(ZF = 0)
+    Ne,
+    // Below or equal(CF = 1 or ZF = 1)
+    Be,
+    // Above(CF = 0 and ZF = 0)
+    A,
+    // Sign(SF = 1)
+    S,
+    // Not sign(SF = 0)
+    Ns,
+    // Parity even(PF = 1)
+    P,
+    // Parity odd(PF = 0)
+    Np,
+    // Less(SF != OF)
+    L,
+    // Greater or equal(SF = OF)
+    Ge,
+    // Less or equal(ZF = 1 or SF != OF)
+    Le,
+    // Greater(ZF = 0 and SF = OF)
+    G,
+}
+
+// The spellings of each condition, in the order of the `Condition` variants. The first spelling is
+// the one used by the Intel manual and by objdump.
+const SPELLINGS: [&[&str]; 16] = [
+    &["o"],
+    &["no"],
+    &["b", "c", "nae"],
+    &["ae", "nb", "nc"],
+    &["e", "z"],
+    &["ne", "nz"],
+    &["be", "na"],
+    &["a", "nbe"],
+    &["s"],
+    &["ns"],
+    &["p", "pe"],
+    &["np", "po"],
+    &["l", "nge"],
+    &["ge", "nl"],
+    &["le", "ng"],
+    &["g", "nle"],
+];
+
+// The mnemonics of `Jcc`, with the spellings of `SPELLINGS`
+const JCC: [&[&str]; 16] = [
+    &["jo"],
+    &["jno"],
+    &["jb", "jc", "jnae"],
+    &["jae", "jnb", "jnc"],
+    &["je", "jz"],
+    &["jne", "jnz"],
+    &["jbe", "jna"],
+    &["ja", "jnbe"],
+    &["js"],
+    &["jns"],
+    &["jp", "jpe"],
+    &["jnp", "jpo"],
+    &["jl", "jnge"],
+    &["jge", "jnl"],
+    &["jle", "jng"],
+    &["jg", "jnle"],
+];
+
+// The mnemonics of `SETcc`, with the spellings of `SPELLINGS`
+const SETCC: [&[&str]; 16] = [
+    &["seto"],
+    &["setno"],
+    &["setb", "setc", "setnae"],
+    &["setae", "setnb", "setnc"],
+    &["sete", "setz"],
+    &["setne", "setnz"],
+    &["setbe", "setna"],
+    &["seta", "setnbe"],
+    &["sets"],
+    &["setns"],
+    &["setp", "setpe"],
+    &["setnp", "setpo"],
+    &["setl", "setnge"],
+    &["setge", "setnl"],
+    &["setle", "setng"],
+    &["setg", "setnle"],
+];
+
+// The mnemonics of `CMOVcc`, with the spellings of `SPELLINGS`
+const CMOVCC: [&[&str]; 16] = [
+    &["cmovo"],
+    &["cmovno"],
+    &["cmovb", "cmovc", "cmovnae"],
+    &["cmovae", "cmovnb", "cmovnc"],
+    &["cmove", "cmovz"],
+    &["cmovne", "cmovnz"],
+    &["cmovbe", "cmovna"],
+    &["cmova", "cmovnbe"],
+    &["cmovs"],
+    &["cmovns"],
+    &["cmovp", "cmovpe"],
+    &["cmovnp", "cmovpo"],
+    &["cmovl", "cmovnge"],
+    &["cmovge", "cmovnl"],
+    &["cmovle", "cmovng"],
+    &["cmovg", "cmovnle"],
+];
+
+impl From<u8> for Condition {
+    fn from(value: u8) -> Self {
+        match value & 0xf {
+            0x0 => Self::O,
+            0x1 => Self::No,
+            0x2 => Self::B,
+            0x3 => Self::Ae,
+            0x4 => Self::E,
+            0x5 => Self::Ne,
+            0x6 => Self::Be,
+            0x7 => Self::A,
+            0x8 => Self::S,
+            0x9 => Self::Ns,
+            0xa => Self::P,
+            0xb => Self::Np,
+            0xc => Self::L,
+            0xd => Self::Ge,
+            0xe => Self::Le,
+            0xf => Self::G,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spellings()[0])
+    }
+}
+
+impl Condition {
+    /// Returns the condition which holds exactly when this one does not, like `ne` for `e`
+    pub fn invert(&self) -> Self {
+        Self::from(*self as u8 ^ 1)
+    }
+
+    /// Returns every way assemblers spell the condition, like `b`, `c` and `nae`
+    pub fn spellings(&self) -> &'static [&'static str] {
+        SPELLINGS[*self as usize]
+    }
+
+    /// Returns the flags the condition reads
+    pub fn tested_flags(&self) -> Flags {
+        match self {
+            Self::O | Self::No => Flags::OF,
+            Self::B | Self::Ae => Flags::CF,
+            Self::E | Self::Ne => Flags::ZF,
+            Self::Be | Self::A => Flags::CF | Flags::ZF,
+            Self::S | Self::Ns => Flags::SF,
+            Self::P | Self::Np => Flags::PF,
+            Self::L | Self::Ge => Flags::SF | Flags::OF,
+            Self::Le | Self::G => Flags::ZF | Flags::SF | Flags::OF,
+        }
+    }
+
+    /// Returns whether the condition holds, when the flags in `rflags` are set and all the others
+    /// are cleared
+    pub fn evaluate(&self, rflags: Flags) -> bool {
+        let less = rflags.contains(Flags::SF) != rflags.contains(Flags::OF);
+        // Every odd condition is the inverse of the even one before it
+        let holds = match self {
+            Self::O | Self::No => rflags.contains(Flags::OF),
+            Self::B | Self::Ae => rflags.contains(Flags::CF),
+            Self::E | Self::Ne => rflags.contains(Flags::ZF),
+            Self::Be | Self::A => rflags.contains(Flags::CF) || rflags.contains(Flags::ZF),
+            Self::S | Self::Ns => rflags.contains(Flags::SF),
+            Self::P | Self::Np => rflags.contains(Flags::PF),
+            Self::L | Self::Ge => less,
+            Self::Le | Self::G => rflags.contains(Flags::ZF) || less,
+        };
+        holds != (*self as u8 & 1 == 1)
+    }
+}
+
+/// The spelling to use for each condition, when printing the mnemonic of a conditional
+/// instruction. Defaults to the spellings of the Intel manual, which objdump also uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConditionAliases([u8; 16]);
+
+impl ConditionAliases {
+    /// Prefers `spelling`, like `z` or `nae`, for the condition it spells. Returns `None` if it
+    /// does not spell any condition.
+    pub fn prefer(mut self, spelling: &str) -> Option<Self> {
+        let (condition, alias) =
+            SPELLINGS
+                .iter()
+                .enumerate()
+                .find_map(|(condition, spellings)| {
+                    let alias = spellings.iter().position(|alias| *alias == spelling)?;
+                    Some((condition, alias))
+                })?;
+        self.0[condition] = alias as u8;
+        Some(self)
+    }
+
+    /// Returns the preferred spelling of `condition`
+    pub fn spelling(&self, condition: Condition) -> &'static str {
+        condition.spellings()[usize::from(self.0[condition as usize])]
+    }
+
+    /// Returns the mnemonic of the conditional instruction `ident`, with the preferred spelling of
+    /// its condition, or `None` if the instruction is not conditional
+    pub fn mnemonic(&self, ident: OpcodeType) -> Option<&'static str> {
+        let (mnemonics, condition) = match ident {
+            OpcodeType::Jcc(condition) => (&JCC, condition),
+            OpcodeType::Setcc(condition) => (&SETCC, condition),
+            OpcodeType::Cmovcc(condition) => (&CMOVCC, condition),
+            _ => return None,
+        };
+        Some(mnemonics[condition as usize][usize::from(self.0[condition as usize])])
+    }
+}
+
+impl OpcodeType {
+    /// Returns the condition of a conditional instruction
+    pub fn condition(&self) -> Option<Condition> {
+        match self {
+            Self::Jcc(condition) | Self::Setcc(condition) | Self::Cmovcc(condition) => {
+                Some(*condition)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Module that tells what immediates mean beyond their hexadecimal value, such that decoded
+//! shellcode and libc internals read faster. An immediate is described by its decimal value, the
+//! characters of its printable bytes and its negative value, like `47 '/'`, and the immediates
+//! whose use is known are named after it, like the Linux system call numbers moved into `eax`
+//! before `syscall`.
+use crate::{
+    arch::Arch,
+    class::Category,
+    dis::Decoded,
+    imm::Immediate,
+    inst::{Instruction, ResolvedOperand},
+    opcode::OpcodeType,
+    reg::{Reg, RegFamily},
+};
+use std::collections::BTreeMap;
+
+// The numbers of the Linux system calls of x86-64
+const SYSCALLS: [(u64, &str); 103] = [
+    (0, "read"),
+    (1, "write"),
+    (2, "open"),
+    (3, "close"),
+    (4, "stat"),
+    (5, "fstat"),
+    (6, "lstat"),
+    (7, "poll"),
+    (8, "lseek"),
+    (9, "mmap"),
+    (10, "mprotect"),
+    (11, "munmap"),
+    (12, "brk"),
+    (13, "rt_sigaction"),
+    (14, "rt_sigprocmask"),
+    (15, "rt_sigreturn"),
+    (16, "ioctl"),
+    (17, "pread64"),
+    (18, "pwrite64"),
+    (19, "readv"),
+    (20, "writev"),
+    (21, "access"),
+    (22, "pipe"),
+    (23, "select"),
+    (24, "sched_yield"),
+    (25, "mremap"),
+    (26, "msync"),
+    (27, "mincore"),
+    (28, "madvise"),
+    (29, "shmget"),
+    (30, "shmat"),
+    (31, "shmctl"),
+    (32, "dup"),
+    (33, "dup2"),
+    (34, "pause"),
+    (35, "nanosleep"),
+    (36, "getitimer"),
+    (37, "alarm"),
+    (38, "setitimer"),
+    (39, "getpid"),
+    (40, "sendfile"),
+    (41, "socket"),
+    (42, "connect"),
+    (43, "accept"),
+    (44, "sendto"),
+    (45, "recvfrom"),
+    (46, "sendmsg"),
+    (47, "recvmsg"),
+    (48, "shutdown"),
+    (49, "bind"),
+    (50, "listen"),
+    (51, "getsockname"),
+    (52, "getpeername"),
+    (53, "socketpair"),
+    (54, "setsockopt"),
+    (55, "getsockopt"),
+    (56, "clone"),
+    (57, "fork"),
+    (58, "vfork"),
+    (59, "execve"),
+    (60, "exit"),
+    (61, "wait4"),
+    (62, "kill"),
+    (63, "uname"),
+    (72, "fcntl"),
+    (74, "fsync"),
+    (77, "ftruncate"),
+    (78, "getdents"),
+    (79, "getcwd"),
+    (80, "chdir"),
+    (82, "rename"),
+    (83, "mkdir"),
+    (84, "rmdir"),
+    (86, "link"),
+    (87, "unlink"),
+    (88, "symlink"),
+    (89, "readlink"),
+    (90, "chmod"),
+    (92, "chown"),
+    (95, "umask"),
+    (96, "gettimeofday"),
+    (101, "ptrace"),
+    (102, "getuid"),
+    (104, "getgid"),
+    (105, "setuid"),
+    (106, "setgid"),
+    (107, "geteuid"),
+    (108, "getegid"),
+    (110, "getppid"),
+    (157, "prctl"),
+    (158, "arch_prctl"),
+    (186, "gettid"),
+    (202, "futex"),
+    (217, "getdents64"),
+    (218, "set_tid_address"),
+    (228, "clock_gettime"),
+    (230, "clock_nanosleep"),
+    (231, "exit_group"),
+    (257, "openat"),
+    (262, "newfstatat"),
+    (302, "prlimit64"),
+    (318, "getrandom"),
+    (322, "execveat"),
+];
+
+// The `open` flags of Linux on x86-64, besides the access mode in the 2 lowest bits
+const OPEN_FLAGS: [(u64, &str); 13] = [
+    (0o100, "O_CREAT"),
+    (0o200, "O_EXCL"),
+    (0o400, "O_NOCTTY"),
+    (0o1000, "O_TRUNC"),
+    (0o2000, "O_APPEND"),
+    (0o4000, "O_NONBLOCK"),
+    (0o10000, "O_DSYNC"),
+    (0o40000, "O_DIRECT"),
+    (0o100000, "O_LARGEFILE"),
+    (0o200000, "O_DIRECTORY"),
+    (0o400000, "O_NOFOLLOW"),
+    (0o2000000, "O_CLOEXEC"),
+    (0o10000000, "O_PATH"),
+];
+
+// The numbers of the `open` and `openat` system calls
+const SYS_OPEN: u64 = 2;
+const SYS_OPENAT: u64 = 257;
+
+/// Returns the name of the Linux system call of x86-64 numbered `number`
+pub fn syscall_name(number: u64) -> Option<&'static str> {
+    SYSCALLS
+        .iter()
+        .find(|(syscall, _)| *syscall == number)
+        .map(|(_, name)| *name)
+}
+
+/// Returns the `open` flags set in `flags`, like `O_WRONLY|O_CREAT|O_TRUNC`. The bits without a
+/// name are written in hexadecimal.
+pub fn open_flags(flags: u64) -> String {
+    let mut names = vec![match flags & 0b11 {
+        0 => "O_RDONLY".to_string(),
+        1 => "O_WRONLY".to_string(),
+        2 => "O_RDWR".to_string(),
+        mode => format!("0x{mode:x}"),
+    }];
+    let mut rest = flags & !0b11;
+    for (flag, name) in OPEN_FLAGS {
+        if rest & flag != 0 {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("0x{rest:x}"));
+    }
+    names.join("|")
+}
+
+/// Returns what the immediates of `inst` mean beyond their hexadecimal value: their decimal value,
+/// the characters of their bytes if all of them are printable, and their negative value if they
+/// are large unsigned ones, like `47 '/'` or `-1`. `name` is added for an immediate whose use is
+/// known. Returns `None` if there is nothing to add.
+pub fn immediate_comment(inst: &Instruction, name: Option<&str>) -> Option<String> {
+    let mut parts = vec![];
+    for operand in inst.operands.explicit() {
+        let ResolvedOperand::Immediate(imm) = operand.operand else {
+            continue;
+        };
+        // Small numbers read the same in decimal
+        match imm.signed_value() {
+            value if value < 0 => parts.push(value.to_string()),
+            _ if imm.value() >= 10 => parts.push(imm.value().to_string()),
+            _ => {}
+        }
+
+        let len = Immediate::encoded_len(imm.encoded_size());
+        let bytes = &imm.raw().to_le_bytes()[..len];
+        if bytes.iter().all(|byte| (0x20..0x7f).contains(byte)) {
+            let text: String = bytes.iter().map(|byte| char::from(*byte)).collect();
+            match len {
+                1 => parts.push(format!("'{text}'")),
+                _ => parts.push(format!("\"{text}\"")),
+            }
+        }
+    }
+    parts.extend(name.map(str::to_string));
+    match parts.is_empty() {
+        true => None,
+        false => Some(parts.join(" ")),
+    }
+}
+
+/// Returns the names of the immediates of `decoded` whose use is known, by the offset of their
+/// instruction: the numbers of the Linux system calls moved into `eax` before `syscall`, and the
+/// flags of the `open` and `openat` system calls. Only the instructions since the last branch are
+/// followed, since the registers are unknown across one.
+pub fn constant_names(decoded: &[Decoded]) -> BTreeMap<usize, String> {
+    let mut names = BTreeMap::new();
+    for (idx, item) in decoded.iter().enumerate() {
+        let Decoded::Inst { inst, .. } = item else {
+            continue;
+        };
+        if inst.opcode.ident != OpcodeType::Syscall || inst.cpu_mode() != Arch::Arch64 {
+            continue;
+        }
+        let Some((offset, number)) = last_immediate(&decoded[..idx], RegFamily::Accumulator) else {
+            continue;
+        };
+        let Some(name) = syscall_name(number) else {
+            continue;
+        };
+        names.insert(offset, name.to_string());
+
+        // The flags are the second argument of `open`, in rsi, and the third of `openat`, in rdx
+        let flags_reg = match number {
+            SYS_OPEN => RegFamily::Source,
+            SYS_OPENAT => RegFamily::Data,
+            _ => continue,
+        };
+        if let Some((offset, flags)) = last_immediate(&decoded[..idx], flags_reg) {
+            names.insert(offset, open_flags(flags));
+        }
+    }
+    names
+}
+
+// Returns the offset of the instruction which last wrote the register of `family` in `decoded`,
+// and the immediate it wrote, if it moved an immediate into the register
+fn last_immediate(decoded: &[Decoded], family: RegFamily) -> Option<(usize, u64)> {
+    let is_target = |reg: &Reg| reg.family() == Some(family);
+    for item in decoded.iter().rev() {
+        let Decoded::Inst { offset, inst, .. } = item else {
+            return None;
+        };
+        let info = inst.opcode.ident.info();
+        if info.is_branch() || info.category == Category::System {
+            return None;
+        }
+        if !inst.register_sets().written.iter().any(is_target) {
+            continue;
+        }
+
+        let mut operands = inst.operands.explicit();
+        return match (
+            inst.opcode.ident,
+            operands.next().map(|op| op.operand),
+            operands.next().map(|op| op.operand),
+        ) {
+            (
+                OpcodeType::Mov,
+                Some(ResolvedOperand::Reg(reg)),
+                Some(ResolvedOperand::Immediate(imm)),
+            ) if is_target(&reg) => Some((*offset, imm.value())),
+            _ => None,
+        };
+    }
+    None
+}
@@ -0,0 +1,235 @@
+//! Describes the state of the processor that the decoder has to follow, in order to interpret the
+//! instruction bytes the same way the processor would
+use crate::{
+    arch::Arch,
+    cond::ConditionAliases,
+    opcode::{AddrSize, OpSize},
+};
+
+/// The operating mode of the processor. Besides the mode itself, the default operand and address
+/// sizes are given by the L and D fields of the current code segment descriptor(CS.L and CS.D)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Real-address mode, where the default operand and address sizes are always 16-bit
+    Real,
+    // Protected mode with a 16-bit code segment(CS.D = 0)
+    Protected16,
+    // Protected mode with a 32-bit code segment(CS.D = 1)
+    Protected32,
+    // Compatibility mode(IA-32e mode with CS.L = 0) with a 16-bit code segment(CS.D = 0)
+    Compat16,
+    // Compatibility mode(IA-32e mode with CS.L = 0) with a 32-bit code segment(CS.D = 1)
+    Compat32,
+    // 64-bit mode(IA-32e mode with CS.L = 1)
+    Long,
+}
+
+impl Mode {
+    /// Returns the mode given by the state of IA-32e mode(EFER.LMA) and the L and D fields of the
+    /// code segment descriptor, in protected mode. Returns `None` for the reserved combination of
+    /// CS.L = 1 and CS.D = 1 in IA-32e mode.
+    pub fn from_code_segment(long_mode_active: bool, cs_l: bool, cs_d: bool) -> Option<Self> {
+        match (long_mode_active, cs_l, cs_d) {
+            (true, true, true) => None,
+            (true, true, false) => Some(Self::Long),
+            (true, false, false) => Some(Self::Compat16),
+            (true, false, true) => Some(Self::Compat32),
+            // Outside of IA-32e mode, the L bit is reserved and ignored
+            (false, _, false) => Some(Self::Protected16),
+            (false, _, true) => Some(Self::Protected32),
+        }
+    }
+
+    /// Returns the width of the code executed in this mode
+    pub fn arch(&self) -> Arch {
+        match self {
+            Self::Real | Self::Protected16 | Self::Compat16 => Arch::Arch16,
+            Self::Protected32 | Self::Compat32 => Arch::Arch32,
+            Self::Long => Arch::Arch64,
+        }
+    }
+}
+
+impl From<Arch> for Mode {
+    fn from(value: Arch) -> Self {
+        match value {
+            Arch::Arch16 => Self::Protected16,
+            Arch::Arch32 => Self::Protected32,
+            Arch::Arch64 => Self::Long,
+        }
+    }
+}
+
+/// The vendor of the processor. Intel and AMD processors decode a couple of instructions
+/// differently in 64-bit mode:
+/// - Near branches with a 0x66 prefix use a 64-bit operand on Intel and a 16-bit one on AMD
+/// - Far branches with REX.W = 1 use a m16:64 pointer on Intel, while AMD ignores REX.W
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+    Intel,
+    Amd,
+}
+
+/// An ISA extension that instructions can belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Extension {
+    X87,
+    Mmx,
+    Sse,
+    Sse2,
+    Sse3,
+    Ssse3,
+    Sse41,
+    Sse42,
+    Avx,
+    Avx2,
+    Avx512F,
+    Bmi1,
+    Bmi2,
+    Lzcnt,
+    Popcnt,
+    // Control-flow Enforcement Technology, Indirect Branch Tracking. Adds `ENDBR32/64`
+    CetIbt,
+    // Conditional moves, `CMOVcc`, added by the P6 family and reported by CPUID.01H:EDX.CMOV
+    Cmov,
+    // Fast system calls, `SYSCALL` and `SYSRET`, reported by CPUID.80000001H:EDX.SYSCALL
+    Syscall,
+}
+
+/// A set of ISA extensions, stored as a bitmap indexed by the `Extension` discriminant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extensions(u32);
+
+impl Extensions {
+    /// Returns a set with no extensions
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns a set with all the extensions
+    pub const fn all() -> Self {
+        Self((1 << (Extension::Syscall as u32 + 1)) - 1)
+    }
+
+    pub fn contains(&self, ext: Extension) -> bool {
+        self.0 & (1 << ext as u32) != 0
+    }
+
+    pub fn insert(&mut self, ext: Extension) {
+        self.0 |= 1 << ext as u32;
+    }
+
+    pub fn remove(&mut self, ext: Extension) {
+        self.0 &= !(1 << ext as u32);
+    }
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Configures how the decoder interprets the instruction bytes. Defaults to 64-bit mode on an
+/// Intel processor, with all the extensions enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderConfig {
+    mode: Mode,
+    vendor: Vendor,
+    extensions: Extensions,
+    // How the conditions of the conditional instructions are spelled in their mnemonic
+    condition_aliases: ConditionAliases,
+}
+
+impl DecoderConfig {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            vendor: Vendor::Intel,
+            extensions: Extensions::all(),
+            condition_aliases: ConditionAliases::default(),
+        }
+    }
+
+    pub fn with_vendor(mut self, vendor: Vendor) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn with_condition_aliases(mut self, condition_aliases: ConditionAliases) -> Self {
+        self.condition_aliases = condition_aliases;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn vendor(&self) -> Vendor {
+        self.vendor
+    }
+
+    pub fn extensions(&self) -> Extensions {
+        self.extensions
+    }
+
+    pub fn condition_aliases(&self) -> ConditionAliases {
+        self.condition_aliases
+    }
+
+    /// Returns the width of the code being decoded
+    pub fn arch(&self) -> Arch {
+        self.mode.arch()
+    }
+
+    /// Returns the operand size used when there is no operand size prefix and no REX.W. In 64-bit
+    /// mode, this is still 32-bit, since CS.D has to be 0.
+    pub fn default_op_size(&self) -> OpSize {
+        match self.arch() {
+            Arch::Arch16 => OpSize::U16,
+            Arch::Arch32 | Arch::Arch64 => OpSize::U32,
+        }
+    }
+
+    /// Returns the operand size selected by the operand size prefix, which switches between the
+    /// 16-bit and the 32-bit operand size
+    pub fn override_op_size(&self) -> OpSize {
+        match self.default_op_size() {
+            OpSize::U16 => OpSize::U32,
+            _ => OpSize::U16,
+        }
+    }
+
+    /// Returns the address size used when there is no address size prefix
+    pub fn default_addr_size(&self) -> AddrSize {
+        AddrSize::from(self.arch())
+    }
+
+    /// Returns the address size selected by the address size prefix, which switches between the
+    /// 16-bit and the 32-bit address size, or from 64-bit to 32-bit in 64-bit mode
+    pub fn override_addr_size(&self) -> AddrSize {
+        match self.default_addr_size() {
+            AddrSize::Addr16Bit => AddrSize::Addr32Bit,
+            AddrSize::Addr32Bit => AddrSize::Addr16Bit,
+            AddrSize::Addr64Bit => AddrSize::Addr32Bit,
+        }
+    }
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self::new(Mode::Long)
+    }
+}
+
+impl From<Arch> for DecoderConfig {
+    fn from(value: Arch) -> Self {
+        Self::new(Mode::from(value))
+    }
+}
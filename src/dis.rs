@@ -1,70 +1,137 @@
 //! Module that acts as the core disassembler of the program
-use crate::inst::{Instruction, InstructionError};
-use crate::modrm::Arch;
-use crate::opcode::OpcodeError;
-use crate::reader::{Reader, ReaderError};
-use crate::stringify_opcode_type;
+use crate::arch::Arch;
+use crate::cpu::DecoderConfig;
+use crate::error::DecodeError;
+use crate::inst::Instruction;
+use crate::reader::Reader;
 
 #[derive(Debug)]
 pub struct Disassembler;
 
+/// Specifies what the `Disassembler` does when it cannot decode an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Stop and return the error
+    Abort,
+    /// Record the first byte of the failing instruction as data, skip it and carry on decoding from
+    /// the next byte, similar to objdump's `(bad)` output
+    SkipByte,
+}
+
+/// An item produced while sweeping over the input
 #[derive(Debug)]
-pub enum DisassemblerError {
-    ReaderError(ReaderError),
-    OpcodeError(OpcodeError),
-    InstructionError(InstructionError),
+pub enum Decoded {
+    /// A successfully decoded instruction, together with its offset and its bytes
+    Inst {
+        offset: usize,
+        bytes: Vec<u8>,
+        inst: Box<Instruction>,
+    },
+    /// A byte that could not be decoded as the start of an instruction and is treated as data
+    Data {
+        offset: usize,
+        byte: u8,
+        error: DecodeError,
+    },
 }
 
-impl From<ReaderError> for DisassemblerError {
-    fn from(value: ReaderError) -> Self {
-        Self::ReaderError(value)
+impl Decoded {
+    /// Returns the offset of the item in the input
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Inst { offset, .. } | Self::Data { offset, .. } => *offset,
+        }
     }
-}
 
-impl From<OpcodeError> for DisassemblerError {
-    fn from(value: OpcodeError) -> Self {
-        Self::OpcodeError(value)
+    /// Returns the number of input bytes this item covers
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Inst { bytes, .. } => bytes.len(),
+            Self::Data { .. } => 1,
+        }
     }
-}
 
-impl From<InstructionError> for DisassemblerError {
-    fn from(value: InstructionError) -> Self {
-        Self::InstructionError(value)
+    /// Returns `true` if this item does not cover any bytes. Never the case for a decoded item,
+    /// since each instruction and each data item takes at least one byte.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
 impl Disassembler {
-    pub fn parse(
+    /// Linearly decodes all the bytes left in `reader`, following the processor state in `config`.
+    /// With `Recovery::Abort`, the first instruction that cannot be decoded stops the sweep and
+    /// its error is returned.
+    pub fn decode(
         &self,
         reader: &mut Reader,
-        maybe_arch: Option<Arch>,
-    ) -> Result<(), DisassemblerError> {
-        // Initialize a counter for how many instructions we have parsed
-        let mut parser_insts = 0;
-        while parser_insts < 100 && reader.bytes_unread() > 0 {
-            let arch = if let Some(_) = maybe_arch {
-                maybe_arch
-            } else {
-                Some(Arch::Arch64)
-            };
-            reader.start_recording()?;
-            let instruction = Instruction::from_reader(reader, arch)?;
-            parser_insts += 1;
-            let read_bytes = reader.stop_recording()?;
+        config: &DecoderConfig,
+        recovery: Recovery,
+    ) -> Result<Vec<Decoded>, DecodeError> {
+        let mut decoded = vec![];
 
-            let hex_bytes = read_bytes.iter().fold(String::new(), |acc, x| format!("{acc}{x:02x} "));
+        while reader.bytes_unread() > 0 {
+            let offset = reader.pos();
+            match Instruction::from_reader_with_config(reader, config) {
+                Ok(inst) => decoded.push(Decoded::Inst {
+                    offset,
+                    bytes: reader.bytes_since(offset).to_vec(),
+                    inst: Box::new(inst),
+                }),
+                Err(error) => match recovery {
+                    Recovery::Abort => return Err(error),
+                    Recovery::SkipByte => {
+                        // Go back to the start of the failing instruction and only consume its
+                        // first byte, such that we resynchronise on the very next byte
+                        let byte = reader
+                            .seek(offset)
+                            .and_then(|_| reader.read::<u8>())
+                            .map_err(|_| error.clone())?;
+                        decoded.push(Decoded::Data {
+                            offset,
+                            byte,
+                            error,
+                        });
+                    }
+                },
+            }
+        }
 
-            let ident = instruction.opcode.ident;
+        Ok(decoded)
+    }
+
+    /// Decodes and prints all the bytes left in `reader` for the code width `maybe_arch`, or for
+    /// 64-bit code if `None` is passed, stopping at the first instruction that cannot be decoded
+    pub fn parse(&self, reader: &mut Reader, maybe_arch: Option<Arch>) -> Result<(), DecodeError> {
+        let config = maybe_arch.map(DecoderConfig::from).unwrap_or_default();
+        self.parse_with_recovery(reader, &config, Recovery::Abort)
+    }
+
+    /// Decodes and prints all the bytes left in `reader`, one item per line
+    pub fn parse_with_recovery(
+        &self,
+        reader: &mut Reader,
+        config: &DecoderConfig,
+        recovery: Recovery,
+    ) -> Result<(), DecodeError> {
+        for item in self.decode(reader, config, recovery)? {
+            match item {
+                Decoded::Inst { bytes, inst, .. } => {
+                    let hex_bytes = bytes
+                        .iter()
+                        .fold(String::new(), |acc, x| format!("{acc}{x:02x} "));
 
-            println!(
-                "{0: <30} {1: <10} {2: <10}",
-                hex_bytes,
-                stringify_opcode_type!(ident),
-                instruction.operands,
-            );
+                    println!(
+                        "{0: <30} {1: <10} {2: <10}",
+                        hex_bytes, inst.opcode.mnemonic, inst.operands,
+                    );
+                }
+                Decoded::Data { byte, .. } => {
+                    println!("{0: <30} {1: <10}", format!("{byte:02x} "), "(bad)");
+                }
+            }
         }
 
-        // First we try and read the prefix
         Ok(())
     }
 }
@@ -0,0 +1,236 @@
+//! Module that describes the errors raised while decoding an instruction
+use crate::{
+    cpu::Extension,
+    imm::{DispError, ImmError},
+    inst::MAX_INST_LEN,
+    opcode::OpcodeError,
+    prefix::Prefix,
+    reader::{Reader, ReaderError},
+};
+use core::fmt;
+
+/// The component of an instruction that was being parsed when decoding failed. The variants are in
+/// the order in which the components appear in an encoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeComponent {
+    // Legacy and REX prefixes
+    Prefix,
+    // The 1, 2 or 3-byte opcode
+    Opcode,
+    // The ModR/M byte, also used to extend the opcode of group instructions
+    ModRM,
+    // The Scale-Index-Base byte that follows the ModR/M byte
+    Sib,
+    // The displacement of a memory operand
+    Displacement,
+    // The immediate operand
+    Immediate,
+}
+
+impl fmt::Display for DecodeComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Prefix => "prefix",
+            Self::Opcode => "opcode",
+            Self::ModRM => "ModR/M",
+            Self::Sib => "SIB",
+            Self::Displacement => "displacement",
+            Self::Immediate => "immediate",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The reason for which an instruction could not be decoded
+#[derive(Debug, Clone)]
+pub enum DecodeErrorKind {
+    // The input ended before the instruction was complete. Holds how many more bytes the
+    // component needed, the instruction may need even more after that
+    NotEnoughBytes(usize),
+    // The prefix cannot be used with the opcode that follows it
+    InvalidPrefix(Prefix),
+    // The prefix that the opcode requires is missing
+    MissingPrefix,
+    // The byte does not encode an opcode that we know of
+    InvalidOpcode(u8),
+    // The 3 bytes do not encode an opcode that we know of
+    Invalid3ByteOpcode(u8, u8, u8),
+    // The `reg` field of the ModR/M byte does not select an instruction in the opcode group
+    InvalidExtension { opcode: u8, ext: u8 },
+    // The ModR/M byte does not encode a valid operand for the instruction
+    InvalidModRM(u8),
+    // The instruction has an operand encoded in the ModR/M byte, but it has no ModR/M byte
+    MissingModRM,
+    // The ModR/M byte encodes an addressing form that we cannot represent yet
+    UnsupportedAddressing(u8),
+    // The instruction belongs to an ISA extension which is not enabled in the `DecoderConfig`
+    DisabledExtension(Extension),
+    // The instruction is longer than the 15 bytes the processor accepts
+    TooLong,
+    // Any other error raised by the `Reader`
+    Reader(ReaderError),
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotEnoughBytes(needed) => write!(f, "needs {needed} more byte(s)"),
+            Self::InvalidPrefix(prefix) => write!(f, "prefix {prefix:?} is invalid for the opcode"),
+            Self::MissingPrefix => write!(f, "missing prefix required by the opcode"),
+            Self::InvalidOpcode(byte) => write!(f, "invalid opcode 0x{byte:02x}"),
+            Self::Invalid3ByteOpcode(first, second, third) => {
+                write!(
+                    f,
+                    "invalid opcode 0x{first:02x} 0x{second:02x} 0x{third:02x}"
+                )
+            }
+            Self::InvalidExtension { opcode, ext } => {
+                write!(f, "invalid extension /{ext} for opcode 0x{opcode:02x}")
+            }
+            Self::InvalidModRM(byte) => write!(f, "invalid ModR/M byte 0x{byte:02x}"),
+            Self::MissingModRM => write!(f, "operand needs a ModR/M byte that is not encoded"),
+            Self::UnsupportedAddressing(byte) => {
+                write!(f, "unsupported addressing form in ModR/M byte 0x{byte:02x}")
+            }
+            Self::DisabledExtension(ext) => write!(f, "extension {ext:?} is not enabled"),
+            Self::TooLong => write!(f, "instruction is longer than {MAX_INST_LEN} bytes"),
+            Self::Reader(err) => write!(f, "reader error: {err:?}"),
+        }
+    }
+}
+
+impl From<ReaderError> for DecodeErrorKind {
+    fn from(err: ReaderError) -> Self {
+        match err {
+            ReaderError::NotEnoughBytes(needed) => Self::NotEnoughBytes(needed),
+            _ => Self::Reader(err),
+        }
+    }
+}
+
+impl From<OpcodeError> for DecodeErrorKind {
+    fn from(err: OpcodeError) -> Self {
+        match err {
+            OpcodeError::ReaderError(err) => Self::from(err),
+            OpcodeError::InvalidPrefix(prefix) => Self::InvalidPrefix(prefix),
+            OpcodeError::InexistentPrefix => Self::MissingPrefix,
+            OpcodeError::InvalidOpcode(byte) => Self::InvalidOpcode(byte),
+            OpcodeError::Invalid3ByteOpcode(first, second, third) => {
+                Self::Invalid3ByteOpcode(first, second, third)
+            }
+            OpcodeError::InvalidExtension(opcode, ext) => Self::InvalidExtension { opcode, ext },
+        }
+    }
+}
+
+impl From<DispError> for DecodeErrorKind {
+    fn from(err: DispError) -> Self {
+        match err {
+            DispError::ReaderError(err) => Self::from(err),
+        }
+    }
+}
+
+impl From<ImmError> for DecodeErrorKind {
+    fn from(err: ImmError) -> Self {
+        match err {
+            ImmError::ReaderError(err) => Self::from(err),
+        }
+    }
+}
+
+/// Error raised when an instruction cannot be decoded. Besides the reason, it reports where in the
+/// input the failure happened, which part of the instruction was being parsed and which bytes of
+/// the instruction were consumed until that point.
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    // Offset in the input where the failing instruction starts
+    inst_offset: usize,
+    // Offset in the input where the failing component starts
+    offset: usize,
+    // The bytes of the instruction consumed until the failure
+    bytes: Vec<u8>,
+    // The component that was being parsed
+    component: DecodeComponent,
+    // Why decoding failed
+    kind: DecodeErrorKind,
+}
+
+impl DecodeError {
+    pub fn new(
+        inst_offset: usize,
+        offset: usize,
+        bytes: &[u8],
+        component: DecodeComponent,
+        kind: DecodeErrorKind,
+    ) -> Self {
+        Self {
+            inst_offset,
+            offset,
+            bytes: bytes.to_vec(),
+            component,
+            kind,
+        }
+    }
+
+    /// Builds the error for the `component` starting at `offset`, reporting all the bytes read
+    /// from `reader` since the start of the instruction at `inst_offset`
+    pub fn from_reader(
+        reader: &Reader,
+        inst_offset: usize,
+        offset: usize,
+        component: DecodeComponent,
+        kind: impl Into<DecodeErrorKind>,
+    ) -> Self {
+        Self::new(
+            inst_offset,
+            offset,
+            reader.bytes_since(inst_offset),
+            component,
+            kind.into(),
+        )
+    }
+
+    /// Offset in the input where the failing instruction starts
+    pub fn inst_offset(&self) -> usize {
+        self.inst_offset
+    }
+
+    /// Offset in the input of the component that could not be decoded
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The bytes of the instruction consumed until decoding failed
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn component(&self) -> DecodeComponent {
+        self.component
+    }
+
+    pub fn kind(&self) -> &DecodeErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to decode {} at offset 0x{:x}: {}",
+            self.component, self.offset, self.kind
+        )?;
+        if !self.bytes.is_empty() {
+            write!(f, " (instruction at 0x{:x}, bytes", self.inst_offset)?;
+            for byte in self.bytes.iter() {
+                write!(f, " {byte:02x}")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecodeError {}
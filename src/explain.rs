@@ -0,0 +1,309 @@
+//! Module that breaks decoded instructions down into the fields they are encoded with, such that
+//! each byte can be traced back to its meaning, like `48 = REX.W=1 R=0 X=0 B=0` or
+//! `e5 = ModR/M mod=11 reg=100(rsp) rm=101(rbp)`. `explained.md` describes the same layout in
+//! general, while this gives the actual fields of one instruction.
+use crate::{
+    dis::Decoded,
+    format::Formatter,
+    imm::Displacement,
+    inst::{Instruction, ResolvedOperand, SizedOperand},
+    intel::IntelFormatter,
+    layout::Field,
+    modrm::EffAddrType,
+    opcode::{AddressingMethod, OpSize},
+    prefix::{Group1, Prefix},
+    reg::Reg,
+    source::is_rip_relative,
+    table::OperandSpec,
+};
+use core::fmt;
+
+/// One field of an encoded instruction, with what its bytes mean
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub field: Field,
+    // The bytes of the field, as they are encoded
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, byte) in self.bytes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, " = {}", self.text)
+    }
+}
+
+/// Returns the fields `inst` was decoded from, in the order they are encoded. `bytes` are the
+/// bytes of the instruction.
+pub fn explain(inst: &Instruction, bytes: &[u8]) -> Vec<Explanation> {
+    let layout = inst.layout();
+    let mut explained = vec![];
+    let mut push = |field: Field, text: String| {
+        let bytes = bytes.get(field.range()).unwrap_or_default().to_vec();
+        explained.push(Explanation { field, bytes, text });
+    };
+
+    // Each prefix takes one byte. A REX prefix among the legacy prefixes is ignored.
+    if let Some(prefixes) = layout.prefixes {
+        for offset in prefixes.range() {
+            let Some(byte) = bytes.get(offset) else {
+                continue;
+            };
+            let text = match Prefix::from_byte(*byte) {
+                Some(prefix) => explain_prefix(inst, &prefix),
+                None => "REX prefix, ignored since another prefix follows it".to_string(),
+            };
+            push(Field::new(offset, 1), text);
+        }
+    }
+
+    if let (Some(field), Some(rex)) = (layout.rex, inst.rex()) {
+        let text = format!(
+            "REX.W={} R={} X={} B={}",
+            rex.w(),
+            rex.r(),
+            rex.x(),
+            rex.b()
+        );
+        push(field, text);
+    }
+
+    let opcode_bytes = bytes.get(layout.opcode.range()).unwrap_or_default();
+    push(layout.opcode, explain_opcode(inst, opcode_bytes));
+
+    if let Some(field) = layout.modrm {
+        if let Some(byte) = bytes.get(field.offset) {
+            push(field, explain_modrm(inst, *byte));
+        }
+    }
+    if let Some(field) = layout.sib {
+        if let Some(byte) = bytes.get(field.offset) {
+            push(field, explain_sib(inst, *byte));
+        }
+    }
+    if let (Some(field), Some(disp)) = (layout.disp, inst.disp()) {
+        push(field, explain_disp(inst, &disp, field.len));
+    }
+    if let Some(field) = layout.imm {
+        push(field, explain_imm(inst, field.len));
+    }
+    explained
+}
+
+/// Writes the item `decoded` at `address`, followed by the fields of the instruction one per line,
+/// like:
+///
+/// ```text
+/// 1000: mov rbp, rsp
+///     48 = REX.W=1 R=0 X=0 B=0
+///     89 = MOV Ev,Gv
+///     e5 = ModR/M mod=11 reg=100(rsp) rm=101(rbp)
+/// ```
+///
+/// A byte which could not be decoded is written with the error it failed with.
+pub fn write_explained(out: &mut dyn fmt::Write, decoded: &Decoded, address: u64) -> fmt::Result {
+    match decoded {
+        Decoded::Inst { bytes, inst, .. } => {
+            writeln!(
+                out,
+                "{address:x}: {}",
+                IntelFormatter::default().format(inst)
+            )?;
+            for explanation in explain(inst, bytes) {
+                writeln!(out, "    {explanation}")?;
+            }
+            Ok(())
+        }
+        Decoded::Data { byte, error, .. } => {
+            writeln!(out, "{address:x}: <unknown>")?;
+            writeln!(out, "    {byte:02x} = {error}")
+        }
+    }
+}
+
+// Describes the legacy `prefix` of `inst`
+fn explain_prefix(inst: &Instruction, prefix: &Prefix) -> String {
+    if inst.is_mandatory(prefix) {
+        return format!(
+            "mandatory prefix of {}",
+            inst.opcode.mnemonic.to_uppercase()
+        );
+    }
+    match prefix {
+        Prefix::Group1(Group1::Lock) => "LOCK prefix".to_string(),
+        Prefix::Group1(Group1::Rep) => "REP prefix".to_string(),
+        Prefix::Group1(Group1::RepNE) => "REPNE prefix".to_string(),
+        Prefix::Group2(group) => {
+            format!(
+                "{} segment override",
+                group.segment().to_string().to_uppercase()
+            )
+        }
+        Prefix::OpSize => "operand-size override".to_string(),
+        Prefix::AddrSize => "address-size override".to_string(),
+    }
+}
+
+// Describes the opcode bytes of `inst`, by its mnemonic and the operands of its form in the opcode
+// table, like `MOV Ev,Gv`
+fn explain_opcode(inst: &Instruction, bytes: &[u8]) -> String {
+    let mut text = inst.opcode.mnemonic.to_uppercase();
+    let specs: Vec<String> = inst
+        .opcode
+        .specs
+        .iter()
+        .flatten()
+        .map(OperandSpec::to_string)
+        .collect();
+    if !specs.is_empty() {
+        text.push(' ');
+        text.push_str(&specs.join(","));
+    }
+
+    // The lower 3 bits of the last opcode byte select a register
+    if let (Some(idx), Some(byte)) = (
+        spec_index(inst, |spec| spec == OperandSpec::RegInOpcode),
+        bytes.last(),
+    ) {
+        text.push_str(&format!(" reg={:03b}", byte & 0b111));
+        if let Some(name) = operand_name(inst, idx) {
+            text.push_str(&format!("({name})"));
+        }
+    }
+    text
+}
+
+// Describes the ModRM `byte` of `inst`. The reg field selects a register or extends the opcode,
+// and the r/m field selects a register or the form of a memory address.
+fn explain_modrm(inst: &Instruction, byte: u8) -> String {
+    let (mod_bits, reg, rm) = (byte >> 6, (byte >> 3) & 0b111, byte & 0b111);
+
+    let is_reg = |spec: OperandSpec| matches!(spec, OperandSpec::Map(AddressingMethod::G, _));
+    let reg_text = match spec_index(inst, is_reg).and_then(|idx| operand_name(inst, idx)) {
+        Some(name) => name,
+        // The field extends the opcode of a group, written `/digit` by the Intel manuals
+        None => format!("/{reg}"),
+    };
+
+    let is_rm = |spec: OperandSpec| {
+        matches!(
+            spec,
+            OperandSpec::Map(AddressingMethod::E | AddressingMethod::M, _)
+        )
+    };
+    let rm_operand = spec_index(inst, is_rm).and_then(|idx| inst.operands.explicit().nth(idx));
+    let rm_text = match rm_operand.map(|operand| operand.operand) {
+        Some(ResolvedOperand::Reg(reg)) => reg.to_string(),
+        Some(ResolvedOperand::Mem((eff_addr, _, _))) => {
+            let base = match eff_addr {
+                EffAddrType::Reg(reg) => format!("[{reg}]"),
+                // The 16-bit forms which add 2 registers have no SIB byte
+                EffAddrType::Sib => match (inst.layout().sib, inst.sib()) {
+                    (None, Some(sib)) => format!("[{sib}]"),
+                    _ => "SIB".to_string(),
+                },
+                EffAddrType::None => String::new(),
+            };
+            match (base.is_empty(), inst.layout().disp) {
+                (_, None) => base,
+                (true, Some(disp)) => format!("disp{}", disp.len * 8),
+                (false, Some(disp)) => format!("{base}+disp{}", disp.len * 8),
+            }
+        }
+        _ => "?".to_string(),
+    };
+
+    format!("ModR/M mod={mod_bits:02b} reg={reg:03b}({reg_text}) rm={rm:03b}({rm_text})")
+}
+
+// Describes the SIB `byte` of `inst`, with the registers REX.X and REX.B extend its fields to
+fn explain_sib(inst: &Instruction, byte: u8) -> String {
+    let (scale, index, base) = (byte >> 6, (byte >> 3) & 0b111, byte & 0b111);
+    let name = |reg: Option<Reg>| reg.map_or("none".to_string(), |reg| reg.to_string());
+    let sib = inst.sib();
+    format!(
+        "SIB scale={scale:02b}({}) index={index:03b}({}) base={base:03b}({})",
+        1 << scale,
+        name(sib.and_then(|sib| sib.index())),
+        name(sib.and_then(|sib| sib.base())),
+    )
+}
+
+// Describes the displacement `disp` of `inst`, which takes `len` bytes
+fn explain_disp(inst: &Instruction, disp: &Displacement, len: usize) -> String {
+    let rip_relative = inst
+        .operands
+        .explicit()
+        .any(|operand| is_rip_relative(&operand.operand));
+    match rip_relative {
+        true => format!("disp{} {disp}, relative to the next instruction", len * 8),
+        false => format!("disp{} {disp}", len * 8),
+    }
+}
+
+// Describes the immediate of `inst`, which takes `len` bytes. A relative branch target is an
+// immediate too, added to the address of the next instruction.
+fn explain_imm(inst: &Instruction, len: usize) -> String {
+    let bits = len * 8;
+    let operand = inst.operands.explicit().find(|operand| {
+        matches!(
+            operand.operand,
+            ResolvedOperand::Immediate(_) | ResolvedOperand::Relative(_)
+        )
+    });
+    match operand.map(|operand| operand.operand) {
+        Some(ResolvedOperand::Relative(rel)) => format!(
+            "rel{bits} {}, relative to the next instruction",
+            signed_hex(rel.signed_value())
+        ),
+        Some(ResolvedOperand::Immediate(imm)) => {
+            let extended_bits = match imm.size() {
+                OpSize::U8 | OpSize::I8 => 8,
+                OpSize::U16 | OpSize::I16 => 16,
+                OpSize::U32 | OpSize::I32 => 32,
+                OpSize::U64 | OpSize::I64 => 64,
+                OpSize::CpuMode => bits,
+            };
+            match extended_bits > bits {
+                true => format!(
+                    "imm{bits} {}, sign-extended to {extended_bits} bits",
+                    signed_hex(imm.signed_value())
+                ),
+                false => format!("imm{bits} 0x{:x}", imm.value()),
+            }
+        }
+        _ => format!("imm{bits}"),
+    }
+}
+
+// Returns the index of the first operand of `inst` whose form in the opcode table matches
+fn spec_index(inst: &Instruction, matches: impl Fn(OperandSpec) -> bool) -> Option<usize> {
+    inst.opcode
+        .specs
+        .iter()
+        .flatten()
+        .position(|spec| matches(*spec))
+}
+
+// Returns the name of the explicit operand of `inst` at `idx`, if it is a register
+fn operand_name(inst: &Instruction, idx: usize) -> Option<String> {
+    match inst.operands.explicit().nth(idx)?.operand {
+        ResolvedOperand::Reg(reg) => Some(reg.to_string()),
+        ResolvedOperand::Segment(seg_reg) => Some(seg_reg.to_string()),
+        _ => None,
+    }
+}
+
+// Writes `value` as a hexadecimal number, preceded by `-` if it is negative
+fn signed_hex(value: i64) -> String {
+    match value < 0 {
+        true => format!("-0x{:x}", value.unsigned_abs()),
+        false => format!("0x{value:x}"),
+    }
+}
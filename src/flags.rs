@@ -0,0 +1,218 @@
+//! Module that describes the RFLAGS bits and how each instruction affects them
+use crate::opcode::OpcodeType;
+use core::{fmt, ops};
+
+/// A set of bits of the RFLAGS register
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags(u32);
+
+impl Flags {
+    /// Carry flag
+    pub const CF: Self = Self(1 << 0);
+    /// Parity flag
+    pub const PF: Self = Self(1 << 2);
+    /// Auxiliary carry flag
+    pub const AF: Self = Self(1 << 4);
+    /// Zero flag
+    pub const ZF: Self = Self(1 << 6);
+    /// Sign flag
+    pub const SF: Self = Self(1 << 7);
+    /// Trap flag
+    pub const TF: Self = Self(1 << 8);
+    /// Interrupt enable flag
+    pub const IF: Self = Self(1 << 9);
+    /// Direction flag
+    pub const DF: Self = Self(1 << 10);
+    /// Overflow flag
+    pub const OF: Self = Self(1 << 11);
+
+    /// The status flags, which arithmetic instructions compute from their result
+    pub const STATUS: Self =
+        Self(Self::OF.0 | Self::SF.0 | Self::ZF.0 | Self::AF.0 | Self::PF.0 | Self::CF.0);
+
+    // Names of the flags, from the most significant bit, like they are usually written
+    const NAMES: [(Self, &'static str); 9] = [
+        (Self::OF, "OF"),
+        (Self::DF, "DF"),
+        (Self::IF, "IF"),
+        (Self::TF, "TF"),
+        (Self::SF, "SF"),
+        (Self::ZF, "ZF"),
+        (Self::AF, "AF"),
+        (Self::PF, "PF"),
+        (Self::CF, "CF"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the bits of the set, at their position in RFLAGS
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether all the flags in `other` are part of this set
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl ops::BitOr for Flags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl ops::BitOrAssign for Flags {
+    fn bitor_assign(&mut self, other: Self) {
+        *self = self.union(other);
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name);
+        if let Some(name) = names.next() {
+            write!(f, "{name}")?;
+        }
+        for name in names {
+            write!(f, " {name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How an instruction uses the RFLAGS register. A flag is in at most one of the written sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlagEffects {
+    /// Flags whose value is read by the instruction
+    pub tested: Flags,
+    /// Flags which are set or cleared depending on the result of the instruction
+    pub modified: Flags,
+    /// Flags which are always set to 1
+    pub set: Flags,
+    /// Flags which are always cleared to 0
+    pub cleared: Flags,
+    /// Flags whose value is undefined after the instruction
+    pub undefined: Flags,
+}
+
+impl FlagEffects {
+    /// Returns all the flags the instruction writes, whatever their value
+    pub fn written(&self) -> Flags {
+        self.modified | self.set | self.cleared | self.undefined
+    }
+}
+
+impl OpcodeType {
+    /// Returns how the instruction affects the RFLAGS register. Instructions without any effect,
+    /// like `mov`, or prefixes, return empty sets.
+    pub fn flag_effects(&self) -> FlagEffects {
+        let none = Flags::empty();
+        let effects = |tested, modified, cleared, undefined| FlagEffects {
+            tested,
+            modified,
+            set: none,
+            cleared,
+            undefined,
+        };
+        // The result of the logical instructions only updates SF, ZF and PF
+        let logic = effects(
+            none,
+            Flags::SF | Flags::ZF | Flags::PF,
+            Flags::OF | Flags::CF,
+            Flags::AF,
+        );
+
+        match self {
+            Self::Add | Self::Sub | Self::Cmp | Self::Neg => {
+                effects(none, Flags::STATUS, none, none)
+            }
+            Self::Adc | Self::Sbb => effects(Flags::CF, Flags::STATUS, none, none),
+            Self::And | Self::Or | Self::Xor | Self::Test => logic,
+            // The carry flag is not affected, such that they can be used to update loop counters
+            Self::Inc | Self::Dec => effects(none, Flags::STATUS.difference(Flags::CF), none, none),
+            Self::Mul | Self::Imul => effects(
+                none,
+                Flags::OF | Flags::CF,
+                none,
+                Flags::SF | Flags::ZF | Flags::AF | Flags::PF,
+            ),
+            Self::Div | Self::Idiv => effects(none, none, none, Flags::STATUS),
+            Self::Daa => effects(
+                Flags::AF | Flags::CF,
+                Flags::SF | Flags::ZF | Flags::AF | Flags::PF | Flags::CF,
+                none,
+                Flags::OF,
+            ),
+            Self::Aaa => effects(
+                Flags::AF,
+                Flags::AF | Flags::CF,
+                none,
+                Flags::OF | Flags::SF | Flags::ZF | Flags::PF,
+            ),
+            // The direction flag selects whether rSI and rDI are incremented or decremented
+            Self::Movs | Self::Stos | Self::Lods => effects(Flags::DF, none, none, none),
+            Self::Cmps | Self::Scas => effects(Flags::DF, Flags::STATUS, none, none),
+            Self::Jcc(condition) | Self::Setcc(condition) | Self::Cmovcc(condition) => {
+                effects(condition.tested_flags(), none, none, none)
+            }
+            Self::Cli => effects(none, none, Flags::IF, none),
+            Self::Sti => FlagEffects {
+                set: Flags::IF,
+                ..FlagEffects::default()
+            },
+            // RFLAGS is restored from R11, which holds the flags of the user mode
+            Self::Sysret => effects(
+                none,
+                Flags::STATUS | Flags::TF | Flags::IF | Flags::DF,
+                none,
+                none,
+            ),
+            Self::Prefix(_)
+            | Self::Rex(_)
+            | Self::NeedsModRMExtension(_)
+            | Self::Lea
+            | Self::CallNear
+            | Self::CallFar
+            | Self::RetNear
+            | Self::Enter
+            | Self::JmpNear
+            | Self::JmpFar
+            | Self::Push
+            | Self::Pop
+            | Self::Nop
+            | Self::Xchg
+            | Self::Mov
+            | Self::Movzx
+            | Self::Movsx
+            | Self::Not
+            | Self::EndBr32
+            | Self::EndBr64
+            | Self::Hlt
+            | Self::Clts
+            | Self::Invd
+            | Self::Wbinvd
+            // RFLAGS is saved to R11 and restored on the return to user mode
+            | Self::Syscall => FlagEffects::default(),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Module that renders decoded instructions as text. A `Formatter` implements one assembly syntax,
+//! and its `FormatOptions` select how numbers, names and operands are written, such that one
+//! decoded instruction can be rendered many ways without decoding it again.
+use crate::{
+    constant::immediate_comment,
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    opcode::OpcodeType,
+    prefix::{Group1, Prefix},
+    reg::Reg,
+    symbol::{operand_address, Symbolizer},
+    token::{FormatterOutput, Token, TokenKind, Tokens},
+};
+use core::fmt;
+
+/// How hexadecimal numbers are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexStyle {
+    // With a `0x` prefix, like `0xff`
+    Prefix,
+    // With a `h` suffix, like `0ffh`, as used by MASM
+    Suffix,
+}
+
+/// Options which select how a `Formatter` writes instructions. Defaults to lower case names,
+/// `0x` hexadecimal numbers, a space after commas, memory size keywords and unsigned immediates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    hex_style: HexStyle,
+    // Whether mnemonics, registers and hexadecimal digits are written in upper case
+    uppercase: bool,
+    space_after_comma: bool,
+    // Whether memory operands are preceded by their size, like `qword ptr`
+    memory_size: bool,
+    // Whether immediates are written as signed numbers of the operand size, like `-0x1` instead
+    // of `0xffffffff`
+    signed_immediates: bool,
+    // Whether instructions with immediates are followed by a comment which tells what they mean,
+    // like `; 47 '/'`
+    immediate_comments: bool,
+}
+
+impl FormatOptions {
+    pub fn new() -> Self {
+        Self {
+            hex_style: HexStyle::Prefix,
+            uppercase: false,
+            space_after_comma: true,
+            memory_size: true,
+            signed_immediates: false,
+            immediate_comments: false,
+        }
+    }
+
+    pub fn with_hex_style(mut self, hex_style: HexStyle) -> Self {
+        self.hex_style = hex_style;
+        self
+    }
+
+    pub fn with_uppercase(mut self, uppercase: bool) -> Self {
+        self.uppercase = uppercase;
+        self
+    }
+
+    pub fn with_space_after_comma(mut self, space_after_comma: bool) -> Self {
+        self.space_after_comma = space_after_comma;
+        self
+    }
+
+    pub fn with_memory_size(mut self, memory_size: bool) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    pub fn with_signed_immediates(mut self, signed_immediates: bool) -> Self {
+        self.signed_immediates = signed_immediates;
+        self
+    }
+
+    pub fn with_immediate_comments(mut self, immediate_comments: bool) -> Self {
+        self.immediate_comments = immediate_comments;
+        self
+    }
+
+    pub fn hex_style(&self) -> HexStyle {
+        self.hex_style
+    }
+
+    pub fn uppercase(&self) -> bool {
+        self.uppercase
+    }
+
+    pub fn space_after_comma(&self) -> bool {
+        self.space_after_comma
+    }
+
+    pub fn memory_size(&self) -> bool {
+        self.memory_size
+    }
+
+    pub fn signed_immediates(&self) -> bool {
+        self.signed_immediates
+    }
+
+    pub fn immediate_comments(&self) -> bool {
+        self.immediate_comments
+    }
+
+    /// Writes `value` as a hexadecimal number, which is a token of `kind`
+    pub fn write_hex(
+        &self,
+        out: &mut dyn FormatterOutput,
+        kind: TokenKind,
+        value: u64,
+    ) -> fmt::Result {
+        let digits = match self.uppercase {
+            true => format!("{value:X}"),
+            false => format!("{value:x}"),
+        };
+        let number = match self.hex_style {
+            HexStyle::Prefix => format!("0x{digits}"),
+            // A number which starts with a letter would be read as a name
+            HexStyle::Suffix if digits.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                format!("0{digits}h")
+            }
+            HexStyle::Suffix => format!("{digits}h"),
+        };
+        out.write_token(kind, &number)
+    }
+
+    /// Writes `value` as a hexadecimal number, preceded by `-` if it is negative
+    pub fn write_signed_hex(
+        &self,
+        out: &mut dyn FormatterOutput,
+        kind: TokenKind,
+        value: i64,
+    ) -> fmt::Result {
+        if value < 0 {
+            out.write_token(kind, "-")?;
+        }
+        self.write_hex(out, kind, value.unsigned_abs())
+    }
+
+    /// Writes a mnemonic, a register or a keyword, which is a token of `kind`, in the configured
+    /// case
+    pub fn write_name(
+        &self,
+        out: &mut dyn FormatterOutput,
+        kind: TokenKind,
+        name: &str,
+    ) -> fmt::Result {
+        match self.uppercase {
+            true => out.write_token(kind, &name.to_uppercase()),
+            false => out.write_token(kind, name),
+        }
+    }
+
+    /// Writes the register `reg`, in the configured case
+    pub fn write_reg(&self, out: &mut dyn FormatterOutput, reg: Reg) -> fmt::Result {
+        self.write_name(out, TokenKind::Register, &reg.to_string())
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An assembly syntax, which renders decoded instructions as text. Only the mnemonic and the
+/// operands are syntax specific, while the way they are put together can be overridden.
+pub trait Formatter {
+    /// Returns the options the instructions are written with
+    fn options(&self) -> &FormatOptions;
+
+    /// Writes the mnemonic of `inst`, preceded by its lock and repeat prefixes
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result;
+
+    /// Writes one of the explicit operands of `inst`
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result;
+
+    /// Writes `operand` of `inst`, referring to the address it refers to by `name`. Defaults to
+    /// the name alone, like `<main+0x10>`.
+    fn write_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        _inst: &Instruction,
+        _operand: &InstOperand,
+        name: &str,
+    ) -> fmt::Result {
+        out.write_token(TokenKind::Symbol, &format!("<{name}>"))
+    }
+
+    /// Writes the relative branch `operand` of `inst` by the address it transfers control to,
+    /// `target`, when that address has no name. Defaults to the address alone, like `0x4b20`.
+    fn write_branch_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        _inst: &Instruction,
+        _operand: &InstOperand,
+        target: u64,
+    ) -> fmt::Result {
+        self.options().write_hex(out, TokenKind::Immediate, target)
+    }
+
+    /// Writes `comment` after an instruction. Defaults to `  ; comment`.
+    fn write_comment(&self, out: &mut dyn FormatterOutput, comment: &str) -> fmt::Result {
+        write!(out, "  ")?;
+        out.write_token(TokenKind::Comment, &format!("; {comment}"))
+    }
+
+    /// Returns the operands of `inst` which are written, in the order they are written. Defaults
+    /// to the explicit operands, in the order of the Intel manuals, with the destination first.
+    fn operands<'a>(&self, inst: &'a Instruction) -> Vec<&'a InstOperand> {
+        inst.operands.explicit().collect()
+    }
+
+    /// Returns the width the mnemonic is padded to, such that the operands of consecutive
+    /// instructions are aligned
+    fn mnemonic_width(&self) -> usize {
+        0
+    }
+
+    /// Writes the operands of `inst`, separated by commas
+    fn write_operands(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        for (idx, operand) in self.operands(inst).into_iter().enumerate() {
+            match (idx, self.options().space_after_comma()) {
+                (0, _) => {}
+                (_, true) => write!(out, ", ")?,
+                (_, false) => write!(out, ",")?,
+            }
+            self.write_operand(out, inst, operand)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole instruction: its mnemonic, followed by its operands
+    fn write(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        let mut mnemonic = Tokens::new();
+        self.write_mnemonic(&mut mnemonic, inst)?;
+        if self.operands(inst).is_empty() {
+            return write_padded(out, mnemonic.tokens(), 0);
+        }
+        // The operands follow the mnemonic, padded to its width, after one space
+        let padding = self.mnemonic_width().saturating_sub(mnemonic.width());
+        write_padded(out, mnemonic.tokens(), padding + 1)?;
+        self.write_operands(out, inst)?;
+        write_immediate_comment(out, self, inst, None)
+    }
+
+    /// Writes the whole instruction, decoded at `address`. The operands which refer to an address
+    /// `symbolizer` names are written by `write_target`, the relative branches to the other
+    /// addresses by `write_branch_target`, and the other operands by `write_operand`.
+    fn write_at(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        address: u64,
+        symbolizer: &dyn Symbolizer,
+    ) -> fmt::Result {
+        write_with(out, self, inst, |out, operand| {
+            let Some(target) = operand_address(inst, operand, address) else {
+                return self.write_operand(out, inst, operand);
+            };
+            match symbolizer.symbolize(target) {
+                Some(name) => self.write_target(out, inst, operand, &name),
+                None if matches!(operand.operand, ResolvedOperand::Relative(_)) => {
+                    self.write_branch_target(out, inst, operand, target)
+                }
+                None => self.write_operand(out, inst, operand),
+            }
+        })?;
+        write_immediate_comment(out, self, inst, None)
+    }
+
+    /// Returns the whole instruction, as written by `write`
+    fn format(&self, inst: &Instruction) -> String {
+        let mut out = String::new();
+        // Writing to a `String` never fails
+        let _ = self.write(&mut out, inst);
+        out
+    }
+
+    /// Returns the whole instruction, as written by `write`, split into tokens
+    fn tokens(&self, inst: &Instruction) -> Vec<Token> {
+        let mut out = Tokens::new();
+        // Collecting tokens never fails
+        let _ = self.write(&mut out, inst);
+        out.into_tokens()
+    }
+}
+
+/// Writes `inst` the way `Formatter::write` does, except for the operands `name` returns a name
+/// for, which are written by `Formatter::write_target`
+pub fn write_named<F: Formatter + ?Sized>(
+    out: &mut dyn FormatterOutput,
+    formatter: &F,
+    inst: &Instruction,
+    name: impl Fn(&InstOperand) -> Option<String>,
+) -> fmt::Result {
+    write_with(out, formatter, inst, |out, operand| match name(operand) {
+        Some(name) => formatter.write_target(out, inst, operand, &name),
+        None => formatter.write_operand(out, inst, operand),
+    })
+}
+
+// Writes `inst` the way `Formatter::write` does, with its operands written by `write_operand`
+fn write_with<F: Formatter + ?Sized>(
+    out: &mut dyn FormatterOutput,
+    formatter: &F,
+    inst: &Instruction,
+    write_operand: impl Fn(&mut dyn FormatterOutput, &InstOperand) -> fmt::Result,
+) -> fmt::Result {
+    let mut mnemonic = Tokens::new();
+    formatter.write_mnemonic(&mut mnemonic, inst)?;
+    let operands = formatter.operands(inst);
+    if operands.is_empty() {
+        return write_padded(out, mnemonic.tokens(), 0);
+    }
+    let padding = formatter.mnemonic_width().saturating_sub(mnemonic.width());
+    write_padded(out, mnemonic.tokens(), padding + 1)?;
+
+    for (idx, operand) in operands.into_iter().enumerate() {
+        match (idx, formatter.options().space_after_comma()) {
+            (0, _) => {}
+            (_, true) => write!(out, ", ")?,
+            (_, false) => write!(out, ",")?,
+        }
+        write_operand(out, operand)?;
+    }
+    Ok(())
+}
+
+/// Writes the comment which tells what the immediates of `inst` mean, if the options of
+/// `formatter` ask for it. `name` is the name of an immediate whose use is known.
+pub fn write_immediate_comment<F: Formatter + ?Sized>(
+    out: &mut dyn FormatterOutput,
+    formatter: &F,
+    inst: &Instruction,
+    name: Option<&str>,
+) -> fmt::Result {
+    if !formatter.options().immediate_comments() {
+        return Ok(());
+    }
+    match immediate_comment(inst, name) {
+        Some(comment) => formatter.write_comment(out, &comment),
+        None => Ok(()),
+    }
+}
+
+/// Writes `tokens` to `out` keeping their kinds, followed by `padding` spaces
+pub fn write_padded(
+    out: &mut dyn FormatterOutput,
+    tokens: &[Token],
+    padding: usize,
+) -> fmt::Result {
+    for token in tokens {
+        out.write_token(token.kind, &token.text)?;
+    }
+    write!(out, "{:padding$}", "")
+}
+
+/// Writes the lock and repeat prefixes of `inst`, each followed by a space
+pub fn write_prefixes(
+    out: &mut dyn FormatterOutput,
+    options: &FormatOptions,
+    inst: &Instruction,
+) -> fmt::Result {
+    for prefix in inst.prefixes() {
+        if inst.is_mandatory(prefix) {
+            continue;
+        }
+        let name = match (prefix, inst.opcode.ident) {
+            (Prefix::Group1(Group1::Lock), _) => "lock",
+            // Repeated comparisons stop on a condition, on which the prefix is named
+            (Prefix::Group1(Group1::Rep), OpcodeType::Cmps | OpcodeType::Scas) => "repz",
+            (Prefix::Group1(Group1::Rep), _) => "rep",
+            (Prefix::Group1(Group1::RepNE), _) => "repnz",
+            _ => continue,
+        };
+        options.write_name(out, TokenKind::Prefix, name)?;
+        write!(out, " ")?;
+    }
+    Ok(())
+}
+
+/// An instruction displayed by a `Formatter`
+pub struct Formatted<'a, F: Formatter + ?Sized> {
+    formatter: &'a F,
+    inst: &'a Instruction,
+}
+
+impl Instruction {
+    /// Returns the instruction, displayed by `formatter`
+    pub fn display<'a, F: Formatter + ?Sized>(&'a self, formatter: &'a F) -> Formatted<'a, F> {
+        Formatted {
+            formatter,
+            inst: self,
+        }
+    }
+}
+
+impl<F: Formatter + ?Sized> fmt::Display for Formatted<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.formatter.write(f, self.inst)
+    }
+}
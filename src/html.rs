@@ -0,0 +1,287 @@
+//! Module that writes a disassembly as a self-contained HTML report, such that listings shared in
+//! code reviews and incident reports keep their navigability. Every address is an anchor, the
+//! operands which refer to a decoded address link to it, each line lists the lines which refer to
+//! it, hovering a register highlights its other uses, and the symbols give a table of contents.
+use crate::{
+    dis::Decoded,
+    format::Formatter,
+    inst::Instruction,
+    objdump::Symbol,
+    opcode::OpSize,
+    symbol::{operand_address, SymbolTable, Symbolizer},
+    token::{Token, TokenKind, Tokens},
+};
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+// The style of the report, which colors the tokens like the ANSI renderer does
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+pre { font-family: monospace; line-height: 1.4; }
+a { color: inherit; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.line:target { background: #fff3b0; }
+.addr, .bytes, .xrefs, .comment { color: #808080; }
+.label { font-weight: bold; }
+.prefix { color: #a000a0; }
+.mnemonic { color: #a06000; font-weight: bold; }
+.reg { color: #008080; }
+.reg.highlight { background: #b0e0ff; }
+.imm, .disp { color: #008000; }
+.sym { color: #0000c0; }
+.kw { color: #808080; }
+.error { color: #c00000; }
+";
+
+// Highlights the uses of the register under the mouse, which share the same `data-reg`
+const SCRIPT: &str = "\
+document.querySelectorAll('[data-reg]').forEach(function (reg) {
+  var toggle = function (on) {
+    document.querySelectorAll('[data-reg=\"' + reg.dataset.reg + '\"]').forEach(function (use) {
+      use.classList.toggle('highlight', on);
+    });
+  };
+  reg.addEventListener('mouseenter', function () { toggle(true); });
+  reg.addEventListener('mouseleave', function () { toggle(false); });
+});
+";
+
+/// Writes the `decoded` items of bytes loaded at `address` as an HTML page titled `title`, with
+/// the instructions written by `formatter`. Each of `symbols` which starts at a decoded item labels
+/// it and gets an entry in the table of contents, and names the addresses up to the next symbol.
+/// The other decoded addresses which are referred to are named like `loc_1010`.
+pub fn write_html<F: Formatter + ?Sized>(
+    out: &mut dyn fmt::Write,
+    formatter: &F,
+    title: &str,
+    decoded: &[Decoded],
+    address: u64,
+    symbols: &[Symbol],
+) -> fmt::Result {
+    let anchors: BTreeSet<u64> = decoded
+        .iter()
+        .map(|item| address.wrapping_add(item.offset() as u64))
+        .collect();
+    let end = decoded.last().map_or(address, |item| {
+        address.wrapping_add((item.offset() + item_bytes(item).len()) as u64)
+    });
+
+    // A symbol takes the bytes up to the next one, or up to the end of the decoded bytes
+    let mut functions: Vec<Symbol> = symbols
+        .iter()
+        .filter(|symbol| anchors.contains(&symbol.address))
+        .copied()
+        .collect();
+    functions.sort_by_key(|symbol| symbol.address);
+    let mut table = SymbolTable::new();
+    for (idx, symbol) in functions.iter().enumerate() {
+        let next = functions.get(idx + 1).map_or(end, |next| next.address);
+        table = table.with_symbol(
+            symbol.address,
+            next.wrapping_sub(symbol.address),
+            symbol.name,
+        );
+    }
+    let name = |target: u64| {
+        table
+            .symbolize(target)
+            .or_else(|| anchors.contains(&target).then(|| format!("loc_{target:x}")))
+    };
+
+    // The lines which refer to each decoded address
+    let mut xrefs: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    for item in decoded {
+        let Decoded::Inst { offset, inst, .. } = item else {
+            continue;
+        };
+        let from = address.wrapping_add(*offset as u64);
+        for target in targets(inst, from) {
+            if anchors.contains(&target) {
+                xrefs.entry(target).or_default().push(from);
+            }
+        }
+    }
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", Escaped(title))?;
+    writeln!(out, "<style>\n{STYLE}</style>")?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>{}</h1>", Escaped(title))?;
+    if !functions.is_empty() {
+        writeln!(out, "<nav>")?;
+        writeln!(out, "<h2>Functions</h2>")?;
+        writeln!(out, "<ul>")?;
+        for symbol in &functions {
+            writeln!(
+                out,
+                "<li><a href=\"#{}\">{}</a></li>",
+                anchor(symbol.address),
+                Escaped(symbol.name)
+            )?;
+        }
+        writeln!(out, "</ul>")?;
+        writeln!(out, "</nav>")?;
+    }
+
+    writeln!(out, "<pre>")?;
+    for item in decoded {
+        let from = address.wrapping_add(item.offset() as u64);
+        for symbol in functions.iter().filter(|symbol| symbol.address == from) {
+            writeln!(
+                out,
+                "<span class=\"label\">{}:</span>",
+                Escaped(symbol.name)
+            )?;
+        }
+
+        write!(
+            out,
+            "<span class=\"line\" id=\"{0}\"><a class=\"addr\" href=\"#{0}\">{from:x}:</a> ",
+            anchor(from)
+        )?;
+        let bytes: Vec<String> = item_bytes(item)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        write!(out, "<span class=\"bytes\">{:<24}</span> ", bytes.join(" "))?;
+        match item {
+            Decoded::Inst { inst, .. } => {
+                let mut tokens = Tokens::new();
+                formatter.write_at(&mut tokens, inst, from, &name)?;
+                // The names written for the addresses the operands refer to, with their address
+                let links: Vec<(String, u64)> = targets(inst, from)
+                    .filter(|target| anchors.contains(target))
+                    .filter_map(|target| Some((name(target)?, target)))
+                    .collect();
+                for token in tokens.tokens() {
+                    write_token(out, inst, token, &links)?;
+                }
+            }
+            Decoded::Data { error, .. } => write!(
+                out,
+                "<span class=\"error\" title=\"{}\">(bad)</span>",
+                Escaped(&error.to_string())
+            )?,
+        }
+        if let Some(refs) = xrefs.get(&from) {
+            write!(out, "  <span class=\"xrefs\">; XREF ")?;
+            for (idx, from) in refs.iter().enumerate() {
+                if idx > 0 {
+                    write!(out, ", ")?;
+                }
+                write!(out, "<a href=\"#{}\">{from:x}</a>", anchor(*from))?;
+            }
+            write!(out, "</span>")?;
+        }
+        writeln!(out, "</span>")?;
+    }
+    writeln!(out, "</pre>")?;
+    writeln!(out, "<script>\n{SCRIPT}</script>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+// Returns the id of the anchor of the line at `address`
+fn anchor(address: u64) -> String {
+    format!("x{address:x}")
+}
+
+// Returns the bytes of `item`
+fn item_bytes(item: &Decoded) -> &[u8] {
+    match item {
+        Decoded::Inst { bytes, .. } => bytes,
+        Decoded::Data { byte, .. } => core::slice::from_ref(byte),
+    }
+}
+
+// Returns the addresses the operands of `inst` refer to, if it is decoded at `address`
+fn targets(inst: &Instruction, address: u64) -> impl Iterator<Item = u64> + '_ {
+    inst.operands
+        .explicit()
+        .filter_map(move |operand| operand_address(inst, operand, address))
+}
+
+// Returns the class of the tokens of `kind`, or `None` for the tokens which are not styled
+fn class(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::Prefix => Some("prefix"),
+        TokenKind::Mnemonic => Some("mnemonic"),
+        TokenKind::Register => Some("reg"),
+        TokenKind::Immediate => Some("imm"),
+        TokenKind::MemoryBracket => None,
+        TokenKind::Displacement => Some("disp"),
+        TokenKind::Symbol => Some("sym"),
+        TokenKind::Keyword => Some("kw"),
+        TokenKind::Comment => Some("comment"),
+        TokenKind::Punctuation | TokenKind::Whitespace => None,
+    }
+}
+
+// Writes `token` of `inst`. A symbol which names one of `links` links to its address, and a
+// register is tagged with the 64-bit register of its family, such that `eax` and `rax` highlight
+// each other.
+fn write_token(
+    out: &mut dyn fmt::Write,
+    inst: &Instruction,
+    token: &Token,
+    links: &[(String, u64)],
+) -> fmt::Result {
+    let text = Escaped(&token.text);
+    let Some(class) = class(token.kind) else {
+        return write!(out, "{text}");
+    };
+    match token.kind {
+        TokenKind::Symbol => {
+            let name = token.text.trim_start_matches('<').trim_end_matches('>');
+            match links.iter().find(|(link, _)| link == name) {
+                Some((_, target)) => write!(
+                    out,
+                    "<a class=\"{class}\" href=\"#{}\">{text}</a>",
+                    anchor(*target)
+                ),
+                None => write!(out, "<span class=\"{class}\">{text}</span>"),
+            }
+        }
+        TokenKind::Register => {
+            let name = token.text.trim_start_matches('%');
+            let sets = inst.register_sets();
+            let reg = sets
+                .read
+                .iter()
+                .chain(&sets.written)
+                .find(|reg| reg.to_string().eq_ignore_ascii_case(name));
+            let tag = match reg.and_then(|reg| reg.family()) {
+                Some(family) => family.reg_from(&OpSize::U64).to_string(),
+                None => name.to_lowercase(),
+            };
+            write!(
+                out,
+                "<span class=\"{class}\" data-reg=\"{}\">{text}</span>",
+                Escaped(&tag)
+            )
+        }
+        _ => write!(out, "<span class=\"{class}\">{text}</span>"),
+    }
+}
+
+// Text displayed with the characters HTML gives a meaning escaped
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => write!(f, "&lt;")?,
+                '>' => write!(f, "&gt;")?,
+                '&' => write!(f, "&amp;")?,
+                '"' => write!(f, "&quot;")?,
+                _ => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
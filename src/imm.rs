@@ -1,7 +1,7 @@
 //! Specifies the Displacement and Immediate rules and parsing mechanism
 use crate::{
     inst::SizedOperand,
-    opcode::OpSize,
+    opcode::{AddrSize, OpSize},
     reader::{Reader, ReaderError},
 };
 use core::fmt;
@@ -11,23 +11,48 @@ use core::fmt;
 /// - [reg + displacement]
 /// - [displacmeent]
 /// - [reg * constant + displacement]
+///
 /// Some addressing forms include a displacement immediately following the ModR/M byte (or the SIB
 /// byte if one is present). If a displacement is required, it can be 1, 2, or 4 bytes.
+/// Displacements are signed, such that `[rbp-0x8]` is encoded with a displacement of `-8`.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Displacement {
-    Disp8(u8),
-    Disp16(u16),
-    Disp32(u32),
-    Disp64(u64),
+    Disp8(i8),
+    Disp16(i16),
+    Disp32(i32),
+    Disp64(i64),
 }
 
 impl fmt::Display for Displacement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.value();
+        if value < 0 {
+            write!(f, "-0x{:x}", value.unsigned_abs())
+        } else {
+            write!(f, "0x{:x}", value)
+        }
+    }
+}
+
+impl Displacement {
+    /// Returns the value of the displacement, sign-extended to 64 bits
+    pub fn value(&self) -> i64 {
+        match self {
+            Displacement::Disp8(value) => *value as i64,
+            Displacement::Disp16(value) => *value as i64,
+            Displacement::Disp32(value) => *value as i64,
+            Displacement::Disp64(value) => *value,
+        }
+    }
+
+    /// Returns the bits of the displacement as they are encoded, which is the address a memory
+    /// operand with only a displacement points to
+    pub fn unsigned(&self) -> u64 {
         match self {
-            Displacement::Disp8(value) => write!(f, "0x{:x}", value),
-            Displacement::Disp16(value) => write!(f, "0x{:x}", value),
-            Displacement::Disp32(value) => write!(f, "0x{:x}", value),
-            Displacement::Disp64(value) => write!(f, "0x{:x}", value),
+            Displacement::Disp8(value) => *value as u8 as u64,
+            Displacement::Disp16(value) => *value as u16 as u64,
+            Displacement::Disp32(value) => *value as u32 as u64,
+            Displacement::Disp64(value) => *value as u64,
         }
     }
 }
@@ -44,17 +69,38 @@ pub enum DispArch {
 }
 
 impl DispArch {
+    /// Returns the number of bytes used to encode the displacement
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Bit8 => 1,
+            Self::Bit16 => 2,
+            Self::Bit32 => 4,
+            Self::Bit64 => 8,
+        }
+    }
+
     pub fn read(&self, reader: &mut Reader) -> Result<Displacement, DispError> {
         match self {
-            Self::Bit8 => Ok(Displacement::Disp8(reader.read::<u8>()?)),
-            Self::Bit16 => Ok(Displacement::Disp16(reader.read::<u16>()?)),
-            Self::Bit32 => Ok(Displacement::Disp32(reader.read::<u32>()?)),
-            Self::Bit64 => Ok(Displacement::Disp64(reader.read::<u64>()?)),
+            Self::Bit8 => Ok(Displacement::Disp8(reader.read::<i8>()?)),
+            Self::Bit16 => Ok(Displacement::Disp16(reader.read::<i16>()?)),
+            Self::Bit32 => Ok(Displacement::Disp32(reader.read::<i32>()?)),
+            Self::Bit64 => Ok(Displacement::Disp64(reader.read::<i64>()?)),
         }
     }
 }
 
-#[derive(Debug)]
+// The memory offset of `mov al, [moffs8]` has the size of an address
+impl From<AddrSize> for DispArch {
+    fn from(value: AddrSize) -> Self {
+        match value {
+            AddrSize::Addr16Bit => Self::Bit16,
+            AddrSize::Addr32Bit => Self::Bit32,
+            AddrSize::Addr64Bit => Self::Bit64,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum DispError {
     ReaderError(ReaderError),
 }
@@ -66,99 +112,145 @@ impl From<ReaderError> for DispError {
 }
 
 /// If an instruction specifies an immediate operand, the operand always follows any displacement
-/// bytes. An immediate operand can be 1, 2 or 4 bytes.
-/// Intel 0x86 Immediates are always sign-extended, so they are always signed.
+/// bytes. An immediate operand can be 1, 2 or 4 bytes, and 8 bytes for the `mov r64, imm64` form.
+/// An immediate smaller than its operand is sign-extended to the operand size.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Immediate {
-    ImmU8(u8),
-    ImmU16(u16),
-    ImmU32(u32),
-    ImmU64(u64),
-    ImmI8(i8),
-    ImmI16(i16),
-    ImmI32(i32),
-    ImmI64(i64),
+pub struct Immediate {
+    // The bits of the immediate, as encoded in the instruction
+    raw: u64,
+    // The size of the immediate in the encoding
+    encoded_size: OpSize,
+    // The size of the operand, which is the size of the value the instruction uses
+    size: OpSize,
+    extension: ImmExtension,
+}
+
+/// How an immediate is extended from its encoded size to the operand size
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImmExtension {
+    // The immediate has the size of the operand
+    None,
+    // The immediate is sign-extended to the operand size
+    Sign,
 }
 
 impl fmt::Display for Immediate {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Immediate::ImmU8(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmU16(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmU32(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmU64(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmI8(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmI16(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmI32(value) => write!(f, "0x{:x}", value),
-            Immediate::ImmI64(value) => write!(f, "0x{:x}", value),
-        }
+        write!(f, "0x{:x}", self.value())
     }
 }
 
 impl SizedOperand for Immediate {
     fn size(&self) -> OpSize {
-        match self {
-            Immediate::ImmU8(value) => OpSize::U8,
-            Immediate::ImmU16(value) => OpSize::U16,
-            Immediate::ImmU32(value) => OpSize::U32,
-            Immediate::ImmU64(value) => OpSize::U64,
-            Immediate::ImmI8(value) => OpSize::I8,
-            Immediate::ImmI16(value) => OpSize::I16,
-            Immediate::ImmI32(value) => OpSize::I32,
-            Immediate::ImmI64(value) => OpSize::I64,
-        }
+        self.size
     }
 }
 
 impl Immediate {
     pub fn parse(op_size: &OpSize, reader: &mut Reader) -> Result<Self, ImmError> {
+        let (raw, encoded_size) = match Self::encoded_len(*op_size) {
+            1 => (reader.read::<u8>()? as u64, OpSize::U8),
+            2 => (reader.read::<u16>()? as u64, OpSize::U16),
+            4 => (reader.read::<u32>()? as u64, OpSize::U32),
+            _ => (reader.read::<u64>()?, OpSize::U64),
+        };
+
+        Ok(Self {
+            raw,
+            encoded_size,
+            size: encoded_size,
+            extension: ImmExtension::None,
+        })
+    }
+
+    /// Returns the number of bytes `parse` reads for an immediate of `op_size`, which is given by
+    /// `OperandType::imm_size`
+    pub fn encoded_len(op_size: OpSize) -> usize {
         match op_size {
-            OpSize::U8 => Ok(Immediate::ImmI8(reader.read::<i8>()?)),
-            OpSize::U16 => Ok(Immediate::ImmI16(reader.read::<i16>()?)),
-            OpSize::U32 => Ok(Immediate::ImmI32(reader.read::<i32>()?)),
-            OpSize::U64 => Ok(Immediate::ImmI32(reader.read::<i32>()?)),
-            OpSize::I8 => Ok(Immediate::ImmI8(reader.read::<i8>()?)),
-            OpSize::I16 => Ok(Immediate::ImmI16(reader.read::<i16>()?)),
-            OpSize::I32 => Ok(Immediate::ImmI32(reader.read::<i32>()?)),
-            OpSize::I64 => Ok(Immediate::ImmI32(reader.read::<i32>()?)),
-            OpSize::CpuMode => Ok(Immediate::ImmI32(reader.read::<i32>()?)),
-            _ => {
-                println!("OpSize: {op_size:?}");
-                todo!();
-            }
+            OpSize::U8 | OpSize::I8 => 1,
+            OpSize::U16 | OpSize::I16 => 2,
+            OpSize::U64 | OpSize::I64 => 8,
+            _ => 4,
         }
     }
 
+    /// Sign-extends the immediate to an operand of `op_size`, if the operand is larger
     pub fn convert_with_opsize(self, op_size: OpSize) -> Self {
-        match op_size {
-            OpSize::CpuMode | OpSize::U8 | OpSize::I8 => self,
-            OpSize::U16 | OpSize::I16 => match self {
-                Immediate::ImmU8(value) => Immediate::ImmU16(value as u16),
-                Immediate::ImmI8(value) => Immediate::ImmU16(value as u16),
-                _ => self,
-            },
-            OpSize::U32 | OpSize::I32 => match self {
-                Immediate::ImmU8(value) => Immediate::ImmU32(value as u32),
-                Immediate::ImmI8(value) => Immediate::ImmU32(value as u32),
-                Immediate::ImmU16(value) => Immediate::ImmU32(value as u32),
-                Immediate::ImmI16(value) => Immediate::ImmU32(value as u32),
-                _ => self,
-            },
-            OpSize::U64 | OpSize::I32 => match self {
-                Immediate::ImmU8(value) => Immediate::ImmU64(value as u64),
-                Immediate::ImmI8(value) => Immediate::ImmU64(value as u64),
-                Immediate::ImmU16(value) => Immediate::ImmU64(value as u64),
-                Immediate::ImmI16(value) => Immediate::ImmU64(value as u64),
-                Immediate::ImmU32(value) => Immediate::ImmU64(value as u64),
-                Immediate::ImmI32(value) => Immediate::ImmU64(value as u64),
-                _ => self,
-            },
-            _ => self,
+        let op_size = match op_size {
+            OpSize::I8 => OpSize::U8,
+            OpSize::I16 => OpSize::U16,
+            OpSize::I32 => OpSize::U32,
+            OpSize::I64 => OpSize::U64,
+            op_size => op_size,
+        };
+        if op_size == OpSize::CpuMode || op_size <= self.size {
+            return self;
+        }
+
+        Self {
+            size: op_size,
+            extension: ImmExtension::Sign,
+            ..self
         }
     }
+
+    /// Returns the bits of the immediate, as encoded in the instruction
+    pub fn raw(&self) -> u64 {
+        self.raw
+    }
+
+    /// Returns the size of the immediate in the encoding, which can be smaller than its operand
+    pub fn encoded_size(&self) -> OpSize {
+        self.encoded_size
+    }
+
+    /// Returns how the immediate is extended to the operand size
+    pub fn extension(&self) -> ImmExtension {
+        self.extension
+    }
+
+    /// Returns the value the instruction uses, extended to the operand size
+    pub fn value(&self) -> u64 {
+        let value = match self.extension {
+            ImmExtension::Sign => self.signed_raw() as u64,
+            ImmExtension::None => self.raw,
+        };
+        value & mask(self.size)
+    }
+
+    /// Returns the value the instruction uses, as a signed number of the operand size
+    pub fn signed_value(&self) -> i64 {
+        let value = self.value();
+        match self.size {
+            OpSize::U8 => value as u8 as i8 as i64,
+            OpSize::U16 => value as u16 as i16 as i64,
+            OpSize::U32 => value as u32 as i32 as i64,
+            _ => value as i64,
+        }
+    }
+
+    // Returns the encoded bits, interpreted as a signed number of the encoded size
+    fn signed_raw(&self) -> i64 {
+        match self.encoded_size {
+            OpSize::U8 => self.raw as u8 as i8 as i64,
+            OpSize::U16 => self.raw as u16 as i16 as i64,
+            OpSize::U32 => self.raw as u32 as i32 as i64,
+            _ => self.raw as i64,
+        }
+    }
+}
+
+// Returns the mask of the bits of a value of `op_size`
+fn mask(op_size: OpSize) -> u64 {
+    match op_size {
+        OpSize::U8 | OpSize::I8 => 0xff,
+        OpSize::U16 | OpSize::I16 => 0xffff,
+        OpSize::U32 | OpSize::I32 => 0xffff_ffff,
+        _ => u64::MAX,
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ImmError {
     ReaderError(ReaderError),
 }
@@ -1,14 +1,21 @@
 use crate::{
-    imm::{DispArch, DispError, Displacement, ImmError, Immediate},
-    modrm::{Arch, EffAddrType, ModRM, Sib, Sib32, Sib64},
+    arch::Arch,
+    cpu::{DecoderConfig, Vendor},
+    error::{DecodeComponent, DecodeError, DecodeErrorKind},
+    flags::{FlagEffects, Flags},
+    imm::{DispArch, Displacement, Immediate},
+    intel::IntelFormatter,
+    layout::{Field, Layout},
+    modrm::{EffAddrType, ModRM, Sib},
     opcode::{
-        AddrSize, OpSize, Opcode, OpcodeError, OpcodeType, Operand, OperandEncoding, RegFieldExt,
-        RegFieldExtError,
+        Access, AddrSize, OpSize, Opcode, OpcodeError, OpcodeType, Operand, OperandEncoding,
+        RegFieldExt,
     },
-    prefix::Prefix,
-    reader::{Reader, ReaderError},
+    prefix::{Group1, Prefix},
+    reader::Reader,
     reg::{Reg, RegFamily, SegmentRegister},
     rex::Rex,
+    table::MandatoryPrefix,
 };
 use core::fmt;
 
@@ -38,42 +45,260 @@ pub struct Instruction {
     // Number are stored as immediates in an opcode, and there are instruction which also encode
     // them as a 1, 2, 4 or in rare cases 8 bytes.
     imm: Option<Immediate>,
+    // Where each of the above components is encoded in the bytes of the instruction
+    layout: Layout,
+    // The operand-size attribute, which is also the size of the instruction pointer after a
+    // relative branch
+    op_size: OpSize,
+    // The address-size attribute, which is the size of the effective address of memory operands
+    addr_size: AddrSize,
+    // The width of the code the instruction was decoded as
+    cpu_mode: Arch,
     // After gathering all the required information about parsing the instruction, we need to
     // resolve to the actual operands of the instruction
     pub operands: InstOperands,
 }
 
+/// The maximum number of bytes of an instruction. The processor rejects longer ones, even if they
+/// only repeat prefixes.
+pub const MAX_INST_LEN: usize = 15;
+
+/// The legacy and REX prefixes that come before the opcode of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prefixes {
+    // Legacy prefixes, in the order they were read. Only the first `legacy_len` are valid, such
+    // that reading the prefixes does not allocate. All the bytes but the opcode can be prefixes.
+    legacy: [Prefix; MAX_INST_LEN - 1],
+    legacy_len: usize,
+    // REX prefix, which only counts if it is the last one before the opcode
+    rex: Option<Rex>,
+    // The number of bytes of all the prefixes, including the REX prefixes which are ignored
+    len: usize,
+}
+
+impl Default for Prefixes {
+    fn default() -> Self {
+        Self {
+            legacy: [Prefix::OpSize; MAX_INST_LEN - 1],
+            legacy_len: 0,
+            rex: None,
+            len: 0,
+        }
+    }
+}
+
+impl Prefixes {
+    pub fn legacy(&self) -> &[Prefix] {
+        &self.legacy[..self.legacy_len]
+    }
+
+    pub fn rex(&self) -> Option<Rex> {
+        self.rex
+    }
+
+    /// Returns the number of bytes used to encode the prefixes, including the REX prefixes which
+    /// are ignored because another prefix follows them
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the remaining prefixes and the opcode of the instruction that starts at
+    /// `inst_offset`. An opcode from a group is resolved by peeking at the ModRM.reg field, such
+    /// that the `reader` is always positioned right after the opcode.
+    pub fn read_opcode(
+        &mut self,
+        reader: &mut Reader,
+        inst_offset: usize,
+        config: &DecoderConfig,
+    ) -> Result<Opcode, DecodeError> {
+        // The width of the code we decode
+        let cpu_mode = config.arch();
+
+        // Read the prefixes until we find the opcode, also keeping the offset where it starts
+        let (opcode_offset, mut opcode) = loop {
+            let opcode_offset = reader.pos();
+            let opcode = read_opcode(reader, inst_offset, self.legacy(), cpu_mode)?;
+            if !matches!(opcode.ident, OpcodeType::Prefix(_) | OpcodeType::Rex(_)) {
+                break (opcode_offset, opcode);
+            }
+            // The opcode still has to follow the prefixes
+            if self.len == MAX_INST_LEN - 1 {
+                return Err(too_long_error(reader, inst_offset));
+            }
+            self.len += 1;
+            match opcode.ident {
+                // A REX prefix followed by a legacy prefix is ignored
+                OpcodeType::Prefix(prefix) => {
+                    self.legacy[self.legacy_len] = prefix;
+                    self.legacy_len += 1;
+                    self.rex = None;
+                }
+                // Only the last of consecutive REX prefixes counts
+                OpcodeType::Rex(rex) => self.rex = Some(rex),
+                _ => {}
+            }
+        };
+
+        opcode.convert_with_rex(self.rex, cpu_mode);
+
+        // We need to filter the opcode, yet again to check if we need an extension from the
+        // ModRM byte, which is the next byte
+        if let OpcodeType::NeedsModRMExtension(_) = opcode.ident {
+            // We just peak the modrm byte
+            let modrm_offset = reader.pos();
+            let modrm_byte = reader
+                .peek::<u8>()
+                .map_err(|err| modrm_error(reader, inst_offset, modrm_offset, err))?;
+
+            // Get the reg part from the ModRM byte
+            let reg = RegFieldExt::try_from((modrm_byte >> 3) & 0b111).map_err(|_| {
+                let kind = DecodeErrorKind::InvalidModRM(modrm_byte);
+                modrm_error(reader, inst_offset, modrm_offset, kind)
+            })?;
+
+            opcode
+                .convert_with_ext_arch(reg, cpu_mode)
+                .map_err(|err| modrm_error(reader, inst_offset, modrm_offset, err))?;
+        }
+
+        // Instructions from extensions which are not enabled cannot be decoded
+        if let Some(extension) = opcode.extension {
+            if !config.extensions().contains(extension) {
+                return Err(DecodeError::from_reader(
+                    reader,
+                    inst_offset,
+                    opcode_offset,
+                    DecodeComponent::Opcode,
+                    DecodeErrorKind::DisabledExtension(extension),
+                ));
+            }
+        }
+
+        Ok(opcode)
+    }
+
+    /// Returns the address size of the instruction. The address size override prefix changes the
+    /// addressing form of the ModRM and SIB bytes and what registers resolve the memory operands.
+    pub fn address_size(&self, config: &DecoderConfig) -> AddrSize {
+        match self.legacy().contains(&Prefix::AddrSize) {
+            true => config.override_addr_size(),
+            false => config.default_addr_size(),
+        }
+    }
+
+    /// Returns the addressing form used to parse the ModRM byte. In 64-bit mode we always parse
+    /// the 64-bit form, such that the REX prefix is applied, and we truncate the registers to the
+    /// address size when we resolve the operands.
+    pub fn addressing_arch(&self, config: &DecoderConfig) -> Arch {
+        match (config.arch(), self.address_size(config)) {
+            (_, AddrSize::Addr16Bit) => Arch::Arch16,
+            (Arch::Arch64, _) => Arch::Arch64,
+            (_, _) => Arch::Arch32,
+        }
+    }
+
+    /// Returns the operand-size attribute of the instruction `ident`. This switches the size of
+    /// the operand depending on the CPU mode, the operand size prefix and the REX prefix.
+    pub fn operand_size(&self, config: &DecoderConfig, ident: OpcodeType) -> OpSize {
+        let cpu_mode = config.arch();
+        let mut op_size = OpSize::from_cpu_opcode(cpu_mode, ident);
+
+        // Near and far branches are decoded differently in 64-bit mode by Intel and AMD
+        let near_branch = matches!(
+            ident,
+            OpcodeType::CallNear | OpcodeType::RetNear | OpcodeType::JmpNear | OpcodeType::Jcc(_)
+        );
+        let far_branch = matches!(ident, OpcodeType::CallFar | OpcodeType::JmpFar);
+
+        // In 64-bit mode, far branches default to a m16:32 pointer
+        if far_branch && cpu_mode == Arch::Arch64 {
+            op_size = OpSize::U32;
+        }
+
+        if self.legacy().contains(&Prefix::OpSize) {
+            // Intel ignores the prefix for near branches in 64-bit mode, which always use a 64-bit
+            // operand, while AMD switches them to a 16-bit operand
+            let ignored =
+                near_branch && cpu_mode == Arch::Arch64 && config.vendor() == Vendor::Intel;
+            if !ignored {
+                op_size = config.override_op_size();
+            }
+        }
+
+        // If we have a prefix, with the REX.W = 1 field set, the operand override prefix is
+        // ignored. AMD also ignores REX.W for far branches, which stay m16:32.
+        if let Some(rex) = self.rex {
+            if rex.w() == 1 && !(far_branch && config.vendor() == Vendor::Amd) {
+                op_size = OpSize::U64;
+            }
+        }
+
+        op_size
+    }
+}
+
+/// An operand of the instruction, along with its size and how the instruction accesses it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct InstOperand {
+    pub operand: ResolvedOperand,
+    pub size: OpSize,
+    pub access: Access,
+}
+
+impl InstOperand {
+    pub fn new(operand: ResolvedOperand, size: OpSize, access: Access) -> Self {
+        Self {
+            operand,
+            size,
+            access,
+        }
+    }
+}
+
+impl fmt::Display for InstOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        IntelFormatter::default().write_sized(f, self)
+    }
+}
+
 #[derive(Debug)]
 pub struct InstOperands {
-    op_size: OpSize,
-    operands: [Option<ResolvedOperand>; 4],
+    operands: [Option<InstOperand>; 4],
+    // Registers the instruction accesses without encoding them, like the stack pointer of `push`
+    // or `rdx:rax` of `mul`
+    implicit: [Option<InstOperand>; 4],
+    // The instruction the operands belong to, since the memory operand of `lea` is written
+    // without a size
+    ident: OpcodeType,
+}
+
+impl InstOperands {
+    /// Returns the operands encoded in the instruction, in the order they are written
+    pub fn explicit(&self) -> impl Iterator<Item = &InstOperand> {
+        self.operands.iter().flatten()
+    }
+
+    /// Returns the registers the instruction accesses without encoding them
+    pub fn implicit(&self) -> impl Iterator<Item = &InstOperand> {
+        self.implicit.iter().flatten()
+    }
 }
 
 impl fmt::Display for InstOperands {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut formatted_operands = String::new();
-        for (idx, maybe_operand) in self.operands.iter().enumerate() {
-            if let Some(operand) = maybe_operand {
-                // If our operand is a memory operand, we have to specify its size
-                if let ResolvedOperand::Mem((eff_addr, _, _)) = operand {
-                    match self.op_size {
-                        OpSize::U8 | OpSize::I8 => write!(f, "BYTE ")?,
-                        OpSize::U16 | OpSize::I16 => write!(f, "WORD ")?,
-                        OpSize::U32 | OpSize::I32 => write!(f, "DWORD ")?,
-                        OpSize::U64 | OpSize::I64 => write!(f, "QWORD ")?,
-                        OpSize::CpuMode => write!(f, "CpuMode ")?,
-                    };
-                    write!(f, "PTR ");
-                }
-                if idx == 0 {
-                    formatted_operands = format!("{operand}");
-                } else {
-                    formatted_operands = format!("{formatted_operands}, {operand}");
-                }
+        let intel = IntelFormatter::default();
+        for (idx, operand) in self.explicit().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
             }
+            intel.write_operand_of(f, self.ident, operand)?;
         }
 
-        write!(f, "{}", formatted_operands)
+        Ok(())
     }
 }
 
@@ -83,36 +308,14 @@ pub enum ResolvedOperand {
     Reg(Reg),
     Segment(SegmentRegister),
     Mem((EffAddrType, Option<Sib>, Option<Displacement>)),
+    // An offset relative to the next instruction, like the target of `Jcc`
+    Relative(Immediate),
     ToBeDecided,
 }
 
 impl fmt::Display for ResolvedOperand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ResolvedOperand::Immediate(imm) => write!(f, "{}", imm),
-            ResolvedOperand::Reg(reg) => write!(f, "{}", reg), 
-            ResolvedOperand::Segment(seg_reg) => write!(f, "{}", seg_reg),
-            ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) => {
-                write!(f, "[");
-                match eff_addr {
-                    EffAddrType::Reg(reg) => write!(f, "{}", reg),
-                    EffAddrType::Sib | EffAddrType::None => {
-                        if let Some(sib) = maybe_sib {
-                            write!(f, "{}", sib)?;
-                            if maybe_disp.is_some() && !sib.is_empty() {
-                                write!(f, "+")?;
-                            }
-                        }
-                        if let Some(disp) = maybe_disp {
-                            write!(f, "{}", disp)?;
-                        }
-                        Ok(())
-                    }
-                };
-                write!(f, "]")
-            }
-            ResolvedOperand::ToBeDecided => write!(f, "UNKNOWN"),
-        }
+        IntelFormatter::default().write_resolved(f, self)
     }
 }
 
@@ -129,12 +332,11 @@ impl SizedOperand for ResolvedOperand {
                 let eff_addr_size = eff_addr.size();
                 match eff_addr_size {
                     OpSize::CpuMode => {
-                        let sib_size = if let Some(sib) = maybe_sib {
+                        if let Some(sib) = maybe_sib {
                             sib.size()
                         } else {
                             eff_addr_size
-                        };
-                        sib_size
+                        }
                     }
                     _ => eff_addr_size,
                 }
@@ -144,69 +346,235 @@ impl SizedOperand for ResolvedOperand {
     }
 }
 
-impl Instruction {
-    pub fn from_reader(
-        reader: &mut Reader,
-        maybe_arch: Option<Arch>,
-    ) -> Result<Self, InstructionError> {
-        // We assume that there is no prefix
-        let mut prefixs = vec![];
-        // We also assume that there is not REX prefix
-        let mut maybe_rex = None;
-        // Declare the default CPU mode
-        let cpu_mode = match maybe_arch {
-            Some(arch) => arch,
-            None => Arch::Arch32,
-        };
+/// The registers an instruction reads and writes, explicitly or implicitly, and how it uses the
+/// RFLAGS register
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegisterSets {
+    pub read: Vec<Reg>,
+    pub written: Vec<Reg>,
+    pub flags: FlagEffects,
+}
 
-        // Try and parse the byte as an Opcode
-        let mut first_opcode = Opcode::with_prefix_arch(reader, &prefixs, cpu_mode)?;
+impl RegisterSets {
+    fn read(&mut self, reg: Reg) {
+        if !self.read.contains(&reg) {
+            self.read.push(reg);
+        }
+    }
 
-        let mut prefix_idx = 0;
-        while let OpcodeType::Prefix(op_prefix) = first_opcode.ident {
-            prefixs.push(op_prefix);
-            first_opcode = Opcode::with_prefix_arch(reader, &prefixs, cpu_mode)?;
-            prefix_idx += 1;
+    fn write(&mut self, reg: Reg) {
+        if !self.written.contains(&reg) {
+            self.written.push(reg);
+        }
+    }
 
-            if prefix_idx == 3 {
-                break;
+    fn access(&mut self, reg: Reg, access: Access) {
+        match access {
+            Access::Read => self.read(reg),
+            Access::Write | Access::Conditional => self.write(reg),
+            Access::ReadWrite => {
+                self.read(reg);
+                self.write(reg);
             }
         }
+    }
+}
 
-        // We may not need this anymore
-        let second_opcode = if let OpcodeType::Prefix(_) = first_opcode.ident {
-            Opcode::with_prefix_arch(reader, &prefixs, cpu_mode)?
-        } else {
-            first_opcode
+impl Instruction {
+    /// Returns the registers the instruction reads and writes, in the order of its operands and
+    /// followed by its implicit registers, along with its effects on RFLAGS. The registers used to
+    /// compute the address of a memory operand are read.
+    pub fn register_sets(&self) -> RegisterSets {
+        let ident = self.opcode.ident;
+        let mut sets = RegisterSets {
+            flags: ident.flag_effects(),
+            ..Default::default()
         };
+        // The address registers of string instructions, which are advanced after every iteration
+        let mut string_regs = vec![];
+
+        for operand in self.operands.explicit().chain(self.operands.implicit()) {
+            match operand.operand {
+                ResolvedOperand::Reg(reg) => sets.access(reg, operand.access),
+                ResolvedOperand::Mem((eff_addr, maybe_sib, _)) => {
+                    let mut addr_regs = vec![];
+                    if let EffAddrType::Reg(reg) = eff_addr {
+                        addr_regs.push(reg);
+                    }
+                    if let Some(sib) = maybe_sib {
+                        addr_regs.extend(sib.base());
+                        addr_regs.extend(sib.index());
+                    }
+                    for reg in addr_regs {
+                        sets.read(reg);
+                        if ident.is_string() {
+                            string_regs.push(reg);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for reg in string_regs.iter() {
+            sets.write(*reg);
+        }
 
-        // At this point we know that the second opcode cannot be a normal prefix.
-        // However, it can be a REX prefix, so we also want to check for that
-        let mut third_opcode = match second_opcode.ident {
-            // If we got a rex prefix, we read again the next opcode
-            OpcodeType::Rex(op_rex) => {
-                // Initialize our own REX
-                maybe_rex = Some(op_rex);
-                Opcode::with_prefix_arch(reader, &prefixs, cpu_mode)?
+        // A repeated string instruction counts down rCX, which has the address size, and the
+        // conditional repeats of `cmps` and `scas` also stop on ZF
+        let repeat = self.prefixs.iter().find_map(|prefix| match prefix {
+            Prefix::Group1(group @ (Group1::Rep | Group1::RepNE)) => Some(*group),
+            _ => None,
+        });
+        if let (Some(_), Some(reg)) = (repeat, string_regs.first()) {
+            let counter = RegFamily::Counter.reg_from(&reg.size());
+            sets.read(counter);
+            sets.write(counter);
+            if matches!(ident, OpcodeType::Cmps | OpcodeType::Scas) {
+                sets.flags.tested |= Flags::ZF;
             }
-            _ => second_opcode,
-        };
+        }
 
-        // Save the ident in a local variable
-        let ident = third_opcode.ident;
+        sets
+    }
 
+    /// Returns the legacy prefixes of the instruction, in the order they were read
+    pub fn prefixes(&self) -> &[Prefix] {
+        &self.prefixs
+    }
 
-        // We need to filter the opcode, yet again to check if we need an extension from the
-        // ModRM byte, which is the next byte
-        if let OpcodeType::NeedsModRMExtension(_) = ident {
-            // We just peak the modrm byte
-            let modrm_byte = reader.peek::<u8>()?;
+    /// Returns whether `prefix` is part of the opcode of the instruction, like the 0xF3 of
+    /// `endbr64`, rather than a legacy prefix which modifies it
+    pub fn is_mandatory(&self, prefix: &Prefix) -> bool {
+        matches!(
+            (prefix, self.opcode.prefix),
+            (Prefix::Group1(Group1::Rep), MandatoryPrefix::PF3)
+                | (Prefix::Group1(Group1::RepNE), MandatoryPrefix::PF2)
+                | (Prefix::OpSize, MandatoryPrefix::P66)
+        )
+    }
 
-            // Get the reg part from the ModRM byte
-            let reg = (modrm_byte >> 3) & 0b111;
+    /// Returns the REX prefix of the instruction, if it has one
+    pub fn rex(&self) -> Option<Rex> {
+        self.rex
+    }
+
+    /// Returns the ModRM byte of the instruction, if it has one
+    pub fn modrm(&self) -> Option<&ModRM> {
+        self.modrm.as_ref()
+    }
+
+    /// Returns the SIB byte of the instruction, if it has one
+    pub fn sib(&self) -> Option<Sib> {
+        self.sib
+    }
 
-            third_opcode.convert_with_ext_arch(RegFieldExt::try_from(reg)?, cpu_mode)?;
+    /// Returns the displacement of the memory operand, if the instruction has one
+    pub fn disp(&self) -> Option<Displacement> {
+        self.disp
+    }
+
+    /// Returns the immediate encoded in the instruction, if it has one
+    pub fn imm(&self) -> Option<Immediate> {
+        self.imm
+    }
+
+    /// Returns the offset and length of each component of the encoded instruction
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Returns the operand-size attribute of the instruction
+    pub fn operand_size(&self) -> OpSize {
+        self.op_size
+    }
+
+    /// Returns the address-size attribute of the instruction
+    pub fn address_size(&self) -> AddrSize {
+        self.addr_size
+    }
+
+    /// Returns the width of the code the instruction was decoded as
+    pub fn cpu_mode(&self) -> Arch {
+        self.cpu_mode
+    }
+
+    /// Returns the address a relative branch, which starts at `address`, transfers control to, or
+    /// `None` if the instruction has no relative offset. With a 16 or 32-bit operand size, the
+    /// instruction pointer wraps around at that size.
+    pub fn branch_target(&self, address: u64) -> Option<u64> {
+        let rel = self
+            .operands
+            .explicit()
+            .find_map(|operand| match operand.operand {
+                ResolvedOperand::Relative(rel) => Some(rel),
+                _ => None,
+            })?;
+        let next = address.wrapping_add(self.layout.len() as u64);
+        let target = next.wrapping_add(rel.signed_value() as u64);
+        match self.op_size {
+            OpSize::U16 | OpSize::I16 => Some(target & 0xffff),
+            OpSize::U32 | OpSize::I32 => Some(target & 0xffff_ffff),
+            _ => Some(target),
         }
+    }
+
+    /// Decodes the next instruction from `reader` for the code width `maybe_arch`, or for 64-bit
+    /// code if `None` is passed. For anything else than the default Intel processor with all the
+    /// extensions enabled, use `from_reader_with_config`.
+    pub fn from_reader(reader: &mut Reader, maybe_arch: Option<Arch>) -> Result<Self, DecodeError> {
+        let config = maybe_arch.map(DecoderConfig::from).unwrap_or_default();
+        Self::from_reader_with_config(reader, &config)
+    }
+
+    /// Decodes the next instruction from `reader`, following the processor state in `config`
+    pub fn from_reader_with_config(
+        reader: &mut Reader,
+        config: &DecoderConfig,
+    ) -> Result<Self, DecodeError> {
+        let inst_offset = reader.pos();
+        Self::from_reader_with_prefixes(reader, inst_offset, config, &mut Prefixes::default())
+    }
+
+    /// Decodes the instruction that starts at `inst_offset`, whose first `prefixes` were already
+    /// read. The `reader` has to be positioned right after them. The remaining prefixes are read
+    /// into `prefixes`, which keeps them even if decoding fails later. This way, an instruction
+    /// that is missing bytes can be resumed without reading its prefixes again.
+    pub fn from_reader_with_prefixes(
+        reader: &mut Reader,
+        inst_offset: usize,
+        config: &DecoderConfig,
+        prefixes: &mut Prefixes,
+    ) -> Result<Self, DecodeError> {
+        // The width of the code we decode
+        let cpu_mode = config.arch();
+
+        let mut third_opcode = prefixes.read_opcode(reader, inst_offset, config)?;
+        // Conditional instructions are spelled the way the configuration prefers
+        if let Some(mnemonic) = config.condition_aliases().mnemonic(third_opcode.ident) {
+            third_opcode.mnemonic = mnemonic;
+        }
+
+        let prefixs = prefixes.legacy().to_vec();
+        let maybe_rex = prefixes.rex;
+
+        // The legacy prefixes start the instruction and the REX prefix comes right before the
+        // opcode, which ends where the reader is now. The REX prefixes which are ignored are
+        // counted with the legacy prefixes.
+        let opcode_offset = prefixes.len();
+        let legacy_len = opcode_offset - usize::from(maybe_rex.is_some());
+        let mut layout = Layout {
+            prefixes: (legacy_len > 0).then(|| Field::new(0, legacy_len)),
+            rex: maybe_rex.map(|_| Field::new(legacy_len, 1)),
+            opcode: Field::new(opcode_offset, reader.pos() - inst_offset - opcode_offset),
+            modrm: None,
+            sib: None,
+            disp: None,
+            imm: None,
+        };
+
+        let addr_size_override = prefixes.address_size(config);
+        let addr_arch = prefixes.addressing_arch(config);
 
         let modrm_encodings = [
             OperandEncoding::M,
@@ -217,6 +585,10 @@ impl Instruction {
 
         // Initialize the ModRM field
         let mut maybe_modrm = None;
+        // Keep the offset and the raw value of the ModRM byte, to report them if the operands it
+        // encodes are invalid
+        let mut modrm_offset = reader.pos();
+        let mut modrm_byte = 0;
         // Initialize the SIB byte
         let mut maybe_sib = None;
         // Initialize the Displacement
@@ -225,60 +597,41 @@ impl Instruction {
         if let Some(encoding) = third_opcode.encoding {
             if modrm_encodings.contains(&encoding) {
                 // We read the modrm byte
-                let modrm_byte = reader.read::<u8>()?;
+                modrm_offset = reader.pos();
+                modrm_byte = reader
+                    .read::<u8>()
+                    .map_err(|err| modrm_error(reader, inst_offset, modrm_offset, err))?;
+                layout.modrm = Some(Field::new(modrm_offset - inst_offset, 1));
 
                 // Parse the ModRM byte
-                let mut modrm = ModRM::from_byte_with_arch(modrm_byte, maybe_arch, maybe_rex);
-
-                // Based on the addressing mode of the CPU, we have to/or not read the SIB byte
-                if let Some(arch) = maybe_arch {
-                    match arch {
-                        // If we have a 32-bit or 64-bit addressing mode, there is a possibility
-                        // that we have a SIB byte
-                        Arch::Arch32 => {
-                            if modrm.1.has_sib() {
-                                let sib_byte = reader.read::<u8>()?;
-                                let mut sib = Sib::Sib32(Sib32::from(sib_byte));
-                                // We know that we have a SIB, so we must take care now of how we
-                                // compute the effective address
-                                if modrm.1.mod_bits() == 0b00 {
-                                    if let Some(Reg::EBP) = sib.base() {
-                                        sib.set_base(None);
-                                        modrm.1.set_displacement(Some(DispArch::Bit32));
-                                    }
-                                }
-
-                                maybe_sib = Some(sib);
-                            }
-                        }
-                        Arch::Arch64 => {
-                            if modrm.1.has_sib() {
-                                let sib_byte = reader.read::<u8>()?;
-                                let mut sib =
-                                    Sib::Sib64(Sib64::from_byte_with_rex(sib_byte, maybe_rex));
-                                // We know that we have a SIB, so we must take care now of how we
-                                // compute the effective address
-                                if modrm.1.mod_bits() == 0b00 {
-                                    if let Some(Reg::RBP) = sib.base() {
-                                        sib.set_base(None);
-                                        modrm.1.set_displacement(Some(DispArch::Bit32));
-                                    }
-                                }
-
-                                maybe_sib = Some(sib);
-                            } else {
-                                // If we do not have a sib, then we must augment the `Reg` from
-                                // the ModRM byte with the REX.B value
-                            }
-                        }
-                        _ => maybe_sib = None,
-                    };
+                let mut modrm = ModRM::from_byte_with_arch(modrm_byte, Some(addr_arch), maybe_rex);
+
+                // In 32-bit or 64-bit addressing mode, there is a possibility that we have a SIB
+                // byte
+                if modrm.1.has_sib() {
+                    layout.sib = Some(Field::new(reader.pos() - inst_offset, 1));
+                    let sib_byte = read_sib(reader, inst_offset)?;
+                    maybe_sib = modrm.sib_from_byte(sib_byte, maybe_rex);
+                } else {
+                    maybe_sib = modrm.1.base_index();
                 }
 
                 if let Some(disp_arch) = modrm.1.displacement() {
-                    let displacement = disp_arch.read(reader)?;
+                    let disp_offset = reader.pos();
+                    let displacement = disp_arch.read(reader).map_err(|err| {
+                        DecodeError::from_reader(
+                            reader,
+                            inst_offset,
+                            disp_offset,
+                            DecodeComponent::Displacement,
+                            err,
+                        )
+                    })?;
                     maybe_disp = Some(displacement);
-                } else {
+                    layout.disp = Some(Field::new(
+                        disp_offset - inst_offset,
+                        reader.pos() - disp_offset,
+                    ));
                 }
 
                 maybe_modrm = Some(modrm);
@@ -288,74 +641,66 @@ impl Instruction {
         // Initialize the immediate value
         let mut maybe_imm = None;
 
-        // Search if there are any immediates in the operands
-        let mut resolved_operands: [Option<ResolvedOperand>; 4] = [None; 4];
-
-        // We need to take into consideration the Operand Size override prefix, when resolving
-        // the operands. This switches the size of the operand depending on the CPU mode and
-        // also the REX prefix
-        let mut op_size_override = OpSize::from_cpu_opcode(cpu_mode, third_opcode.ident);
+        // The operands of the instruction, once we resolve them
+        let mut resolved_operands: [Option<InstOperand>; 4] = [None; 4];
 
-        // We also need to take into consideration the AddressSize override prefix, when
-        // resolving operands which refer to memory.
-        let mut addr_size_override = AddrSize::from(cpu_mode);
+        // The operand-size attribute, which the operands that are not byte sized follow
+        let op_size_override = prefixes.operand_size(config, third_opcode.ident);
 
-        for (idx, op) in third_opcode.operands.iter().enumerate() {
+        for (idx, maybe_op) in third_opcode.operands.iter().enumerate() {
             // We just ignore operands which are `None`
-            if op.is_none() {
+            let Some((op, access)) = maybe_op else {
                 continue;
-            }
-
-            if prefixs.contains(&Prefix::OpSize) {
-                op_size_override = match cpu_mode {
-                    // If we are in 16-bit mode, we use 32-bit operand size
-                    Arch::Arch16 => OpSize::U32,
-                    // If we are in 32-bit mode, we use 16-bit operand size
-                    Arch::Arch32 => OpSize::U16,
-                    // If we are in 64-bit mode, we use 16-bit operand size, however, the prefix
-                    // is ignored if there is a REX prefix with the field REX.X = 1 set.
-                    Arch::Arch64 => OpSize::U16,
-                }
-            }
-            if prefixs.contains(&Prefix::AddrSize) {
-                addr_size_override = match cpu_mode {
-                    // If we are in 16-bit mode, we use 32-bit operand size
-                    Arch::Arch32 | Arch::Arch64 => AddrSize::Addr32Bit,
-                    _ => panic!("Instruction is illegal with the prefix"),
-                }
-            }
-
-            // If we have a prefix, with the REX.X = 1 field set, the operand override prefix is
-            // ignored
-            if let Some(rex) = maybe_rex {
-                if rex.w() == 1 {
-                    op_size_override = OpSize::U64;
-                }
-            }
+            };
 
-            let overridable_op_size = [OpSize::CpuMode, OpSize::U16, OpSize::U32, OpSize::U64];
             let overridable_addr_size = [AddrSize::Addr64Bit];
 
-            match op {
-                Some(Operand::Immediate(op_size)) => {
-                    let mut imm = match overridable_op_size.contains(op_size) {
-                        true => Immediate::parse(&op_size_override, reader)?,
-                        false => Immediate::parse(op_size, reader)?,
-                    };
+            let operand = match op {
+                // A relative offset is encoded just like an immediate
+                Operand::Immediate(op_type) | Operand::Relative(op_type) => {
+                    let imm_offset = reader.pos();
+                    let imm_size = op_type.imm_size(op_size_override);
+                    let mut imm = Immediate::parse(&imm_size, reader).map_err(|err| {
+                        DecodeError::from_reader(
+                            reader,
+                            inst_offset,
+                            imm_offset,
+                            DecodeComponent::Immediate,
+                            err,
+                        )
+                    })?;
                     // We check the size of the last operand, if it was smaller, we extend our
-                    // immediate
+                    // immediate. The second immediate of `enter` is a separate byte.
                     if idx > 0 {
                         if let Some(res_op) = resolved_operands[idx - 1] {
-                            let previous_op_size = res_op.size();
-                            if previous_op_size > imm.size() {
-                                imm = imm.convert_with_opsize(previous_op_size);
+                            let is_imm = matches!(res_op.operand, ResolvedOperand::Immediate(_));
+                            if res_op.size > imm.size() && !is_imm {
+                                imm = imm.convert_with_opsize(res_op.size);
                             }
                         }
                     }
-                    resolved_operands[idx] = Some(ResolvedOperand::Immediate(imm));
+                    // `push` sign-extends its immediate to the operand size, which is the size it
+                    // takes on the stack
+                    if third_opcode.ident == OpcodeType::Push {
+                        imm = imm.convert_with_opsize(op_size_override);
+                    }
+                    maybe_imm = Some(imm);
+                    // Instructions with 2 immediates encode them one after the other
+                    let imm_start = layout
+                        .imm
+                        .map_or(imm_offset - inst_offset, |imm| imm.offset);
+                    layout.imm = Some(Field::new(
+                        imm_start,
+                        reader.pos() - inst_offset - imm_start,
+                    ));
+                    let resolved = match op {
+                        Operand::Relative(_) => ResolvedOperand::Relative(imm),
+                        _ => ResolvedOperand::Immediate(imm),
+                    };
+                    InstOperand::new(resolved, imm.size(), *access)
                 }
                 // Handle the family
-                Some(Operand::RegInOpcode(opcode_byte)) => {
+                Operand::RegInOpcode(opcode_byte) => {
                     let lower_3bits = opcode_byte & 0b111;
                     let reg_64bit_encoding = if let Some(rex) = maybe_rex {
                         lower_3bits | (rex.b() << 3)
@@ -368,77 +713,161 @@ impl Instruction {
                         (Arch::Arch16, OpSize::U16)
                         | (Arch::Arch32, OpSize::U16)
                         | (Arch::Arch64, OpSize::U16) => Arch::Arch16,
-                        (Arch::Arch32, OpSize::U16) => Arch::Arch16,
-                        (Arch::Arch32, OpSize::U32) => Arch::Arch32,
-                        (Arch::Arch64, OpSize::U32) | (Arch::Arch64, OpSize::U64) => Arch::Arch64,
+                        (Arch::Arch16, OpSize::U32)
+                        | (Arch::Arch32, OpSize::U32)
+                        | (Arch::Arch64, OpSize::U32) => Arch::Arch32,
+                        (Arch::Arch64, OpSize::U64) => Arch::Arch64,
                         (_, _) => Arch::Arch64,
                     };
                     let reg_family = RegFamily::from(reg_64bit_encoding);
                     let reg = reg_family.reg_from_arch(&arch);
-                    resolved_operands[idx] = Some(ResolvedOperand::Reg(reg));
+                    InstOperand::new(ResolvedOperand::Reg(reg), reg.size(), *access)
                 }
-                Some(Operand::RegFamily(family)) => {
+                Operand::RegFamily(family) => {
                     let reg = family.reg_from(&op_size_override);
-                    resolved_operands[idx] = Some(ResolvedOperand::Reg(reg));
+                    InstOperand::new(ResolvedOperand::Reg(reg), reg.size(), *access)
                 }
-                Some(Operand::Segment(seg_reg)) => {
-                    resolved_operands[idx] = Some(ResolvedOperand::Segment(*seg_reg))
+                Operand::Segment(seg_reg) => {
+                    InstOperand::new(ResolvedOperand::Segment(*seg_reg), OpSize::U16, *access)
                 }
-                Some(Operand::Reg(reg)) => {
-                    resolved_operands[idx] = Some(ResolvedOperand::Reg(*reg))
+                Operand::Reg(reg) => {
+                    InstOperand::new(ResolvedOperand::Reg(*reg), reg.size(), *access)
                 }
-                Some(Operand::ModRM(op_size, addr_size)) => {
-                    let mut modrm = maybe_modrm
-                        .as_mut()
-                        .ok_or(InstructionError::InvalidModRMError)?;
+                Operand::ModRM(op_size, addr_size) | Operand::Memory(op_size, addr_size) => {
+                    let modrm = maybe_modrm.as_mut().ok_or_else(|| {
+                        modrm_error(
+                            reader,
+                            inst_offset,
+                            modrm_offset,
+                            DecodeErrorKind::MissingModRM,
+                        )
+                    })?;
+                    let op_size = op_size.with_attribute(op_size_override);
+                    // Operands which are only memory, like the address of `lea`, have no register
+                    // form
+                    if modrm.mod_bits() == 0b11 && matches!(op, Operand::Memory(..)) {
+                        let kind = DecodeErrorKind::InvalidModRM(modrm_byte);
+                        return Err(modrm_error(reader, inst_offset, modrm_offset, kind));
+                    }
                     if modrm.mod_bits() == 0b11 {
-                        let reg = modrm.rm_reg().ok_or(InstructionError::InvalidModRMError)?;
-                        let reg = match overridable_op_size.contains(op_size) {
-                            true => reg.convert_with_opsize(&op_size_override),
-                            false => reg.convert_with_opsize(op_size),
+                        let reg = modrm.rm_reg().ok_or_else(|| {
+                            let kind = DecodeErrorKind::InvalidModRM(modrm_byte);
+                            modrm_error(reader, inst_offset, modrm_offset, kind)
+                        })?;
+                        let reg = match maybe_rex {
+                            Some(_) => reg.convert_with_opsize(&op_size),
+                            None => reg.convert_with_opsize(&op_size).without_rex(),
                         };
-                        resolved_operands[idx] = Some(ResolvedOperand::Reg(reg));
+                        InstOperand::new(ResolvedOperand::Reg(reg), reg.size(), *access)
                     } else {
-                        let mem = modrm.rm_mem();
+                        let mem = modrm.rm_mem().ok_or_else(|| {
+                            let kind = DecodeErrorKind::UnsupportedAddressing(modrm_byte);
+                            modrm_error(reader, inst_offset, modrm_offset, kind)
+                        })?;
                         let mem = match overridable_addr_size.contains(addr_size) {
                             true => {
                                 let eff_addr = mem.convert_with_addrsize(addr_size_override);
-                                let sib = if let Some(inner_sib) = maybe_sib {
-                                    Some(inner_sib.convert_with_addrsize(addr_size_override))
-                                } else {
-                                    None
-                                };
+                                let sib = maybe_sib.map(|inner_sib| {
+                                    inner_sib.convert_with_addrsize(addr_size_override)
+                                });
                                 (eff_addr, sib, maybe_disp)
                             }
                             false => {
                                 let eff_addr = mem.convert_with_addrsize(*addr_size);
-                                let sib = if let Some(inner_sib) = maybe_sib {
-                                    Some(inner_sib.convert_with_addrsize(*addr_size))
-                                } else {
-                                    None
-                                };
+                                let sib = maybe_sib
+                                    .map(|inner_sib| inner_sib.convert_with_addrsize(*addr_size));
                                 (eff_addr, sib, maybe_disp)
                             }
                         };
 
-                        resolved_operands[idx] = Some(ResolvedOperand::Mem(mem));
+                        // The size of a memory operand is only given by the instruction
+                        InstOperand::new(ResolvedOperand::Mem(mem), op_size, *access)
                     }
                 }
-                Some(Operand::ModReg(op_size)) => {
-                    let modrm = maybe_modrm
-                        .as_ref()
-                        .ok_or(InstructionError::InvalidModRMError)?;
+                Operand::ModReg(op_size) => {
+                    let modrm = maybe_modrm.as_ref().ok_or_else(|| {
+                        modrm_error(
+                            reader,
+                            inst_offset,
+                            modrm_offset,
+                            DecodeErrorKind::MissingModRM,
+                        )
+                    })?;
                     let reg = modrm.reg();
-                    let reg = match overridable_op_size.contains(&op_size) {
-                        true => reg.convert_with_opsize(&op_size_override),
-                        false => reg.convert_with_opsize(&op_size),
+                    let reg = reg.convert_with_opsize(&op_size.with_attribute(op_size_override));
+                    let reg = match maybe_rex {
+                        Some(_) => reg,
+                        None => reg.without_rex(),
                     };
-                    resolved_operands[idx] = Some(ResolvedOperand::Reg(reg));
+                    InstOperand::new(ResolvedOperand::Reg(reg), reg.size(), *access)
+                }
+                // String instructions address their memory with the rSI or rDI register of the
+                // address size
+                // The offset has the size of an address and it is the whole address of the operand
+                Operand::Offset(op_size) => {
+                    let disp_offset = reader.pos();
+                    let displacement =
+                        DispArch::from(addr_size_override)
+                            .read(reader)
+                            .map_err(|err| {
+                                DecodeError::from_reader(
+                                    reader,
+                                    inst_offset,
+                                    disp_offset,
+                                    DecodeComponent::Displacement,
+                                    err,
+                                )
+                            })?;
+                    maybe_disp = Some(displacement);
+                    layout.disp = Some(Field::new(
+                        disp_offset - inst_offset,
+                        reader.pos() - disp_offset,
+                    ));
+                    let mem = (EffAddrType::None, None, Some(displacement));
+                    let op_size = op_size.with_attribute(op_size_override);
+                    InstOperand::new(ResolvedOperand::Mem(mem), op_size, *access)
                 }
-                _ => resolved_operands[idx] = Some(ResolvedOperand::ToBeDecided),
+                Operand::String(family, op_size) => {
+                    let reg = family.reg_from(&OpSize::from(addr_size_override));
+                    let mem = (EffAddrType::Reg(reg), None, None);
+                    let op_size = op_size.with_attribute(op_size_override);
+                    InstOperand::new(ResolvedOperand::Mem(mem), op_size, *access)
+                }
+                _ => InstOperand::new(ResolvedOperand::ToBeDecided, OpSize::CpuMode, *access),
+            };
+            resolved_operands[idx] = Some(operand);
+        }
+
+        // The registers the instruction accesses without encoding them
+        let mut implicit_operands: [Option<InstOperand>; 4] = [None; 4];
+
+        for (idx, maybe_op) in third_opcode.implicit.iter().enumerate() {
+            let Some((op, access)) = maybe_op else {
+                continue;
             };
+
+            let reg = match op {
+                // The stack pointer has the size of the stack, regardless of the operand size
+                Operand::RegFamily(RegFamily::StackPointer) => {
+                    RegFamily::StackPointer.reg_from(&OpSize::from(cpu_mode))
+                }
+                Operand::RegFamily(family) => family.reg_from(&op_size_override),
+                Operand::Reg(reg) => *reg,
+                _ => continue,
+            };
+            implicit_operands[idx] = Some(InstOperand::new(
+                ResolvedOperand::Reg(reg),
+                reg.size(),
+                *access,
+            ));
         }
 
+        // The prefixes can push the rest of the instruction past the limit
+        if layout.len() > MAX_INST_LEN {
+            return Err(too_long_error(reader, inst_offset));
+        }
+
+        let ident = third_opcode.ident;
         Ok(Instruction {
             prefixs,
             rex: maybe_rex,
@@ -447,48 +876,63 @@ impl Instruction {
             sib: maybe_sib,
             disp: maybe_disp,
             imm: maybe_imm,
-            operands: InstOperands { operands: resolved_operands, op_size: op_size_override},
+            layout,
+            op_size: op_size_override,
+            addr_size: addr_size_override,
+            cpu_mode,
+            operands: InstOperands {
+                operands: resolved_operands,
+                implicit: implicit_operands,
+                ident,
+            },
         })
     }
 }
 
-/// Issues errors for instruction parsing
-#[derive(Debug)]
-pub enum InstructionError {
-    OpcodeError(OpcodeError),
-    ReaderError(ReaderError),
-    RegFieldExtError(RegFieldExtError),
-    DispError(DispError),
-    ImmError(ImmError),
-    InvalidModRMError,
-}
-
-impl From<OpcodeError> for InstructionError {
-    fn from(err: OpcodeError) -> Self {
-        InstructionError::OpcodeError(err)
-    }
+// Builds a `DecodeError` for an instruction that starts at `inst_offset` and is longer than
+// `MAX_INST_LEN`, which is blamed on its prefixes
+fn too_long_error(reader: &Reader, inst_offset: usize) -> DecodeError {
+    DecodeError::from_reader(
+        reader,
+        inst_offset,
+        inst_offset,
+        DecodeComponent::Prefix,
+        DecodeErrorKind::TooLong,
+    )
 }
 
-impl From<ReaderError> for InstructionError {
-    fn from(err: ReaderError) -> Self {
-        InstructionError::ReaderError(err)
-    }
+// Builds a `DecodeError` for an invalid or missing ModRM byte
+fn modrm_error(
+    reader: &Reader,
+    inst_offset: usize,
+    offset: usize,
+    kind: impl Into<DecodeErrorKind>,
+) -> DecodeError {
+    DecodeError::from_reader(reader, inst_offset, offset, DecodeComponent::ModRM, kind)
 }
 
-impl From<RegFieldExtError> for InstructionError {
-    fn from(err: RegFieldExtError) -> Self {
-        InstructionError::RegFieldExtError(err)
-    }
-}
-
-impl From<DispError> for InstructionError {
-    fn from(err: DispError) -> Self {
-        InstructionError::DispError(err)
-    }
+// Reads the next opcode, which could also be a legacy or a REX prefix
+fn read_opcode(
+    reader: &mut Reader,
+    inst_offset: usize,
+    prefixs: &[Prefix],
+    cpu_mode: Arch,
+) -> Result<Opcode, DecodeError> {
+    let offset = reader.pos();
+    Opcode::with_prefix_arch(reader, prefixs, cpu_mode).map_err(|err| {
+        // A prefix which is invalid for the opcode is reported as a prefix error
+        let component = match err {
+            OpcodeError::InvalidPrefix(_) => DecodeComponent::Prefix,
+            _ => DecodeComponent::Opcode,
+        };
+        DecodeError::from_reader(reader, inst_offset, offset, component, err)
+    })
 }
 
-impl From<ImmError> for InstructionError {
-    fn from(err: ImmError) -> Self {
-        InstructionError::ImmError(err)
-    }
+// Reads the SIB byte that follows the ModRM byte
+fn read_sib(reader: &mut Reader, inst_offset: usize) -> Result<u8, DecodeError> {
+    let offset = reader.pos();
+    reader.read::<u8>().map_err(|err| {
+        DecodeError::from_reader(reader, inst_offset, offset, DecodeComponent::Sib, err)
+    })
 }
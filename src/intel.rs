@@ -0,0 +1,185 @@
+//! Module that displays instructions in the Intel syntax, as written in the Intel manuals: the
+//! destination operand comes first and memory operands are written `[base+index*scale+disp]`,
+//! preceded by their size.
+use crate::{
+    format::{write_prefixes, FormatOptions, Formatter},
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::{OpSize, OpcodeType},
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt;
+
+/// Formats instructions in the Intel syntax, like `mov rax, qword ptr [rip+0x10]`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IntelFormatter {
+    options: FormatOptions,
+}
+
+impl IntelFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    /// Writes `operand`, preceded by its size if it is in memory
+    pub fn write_sized(&self, out: &mut dyn FormatterOutput, operand: &InstOperand) -> fmt::Result {
+        self.write_size(out, operand)?;
+        self.write_resolved(out, &operand.operand)
+    }
+
+    /// Writes `operand` of the instruction `ident`, the way `write_operand` does. The address
+    /// computed by `lea` has no size.
+    pub fn write_operand_of(
+        &self,
+        out: &mut dyn FormatterOutput,
+        ident: OpcodeType,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        match ident {
+            OpcodeType::Lea => self.write_resolved(out, &operand.operand),
+            _ => self.write_sized(out, operand),
+        }
+    }
+
+    // Writes the size of `operand` followed by a space, if it is in memory
+    fn write_size(&self, out: &mut dyn FormatterOutput, operand: &InstOperand) -> fmt::Result {
+        // If our operand is a memory operand, we have to specify its size
+        if let (ResolvedOperand::Mem(_), true) = (operand.operand, self.options.memory_size()) {
+            let size = match operand.size {
+                OpSize::U8 | OpSize::I8 => "byte",
+                OpSize::U16 | OpSize::I16 => "word",
+                OpSize::U32 | OpSize::I32 => "dword",
+                OpSize::U64 | OpSize::I64 => "qword",
+                OpSize::CpuMode => return Ok(()),
+            };
+            self.options
+                .write_name(out, TokenKind::Keyword, &format!("{size} ptr"))?;
+            write!(out, " ")?;
+        }
+        Ok(())
+    }
+
+    /// Writes `operand`, without its size
+    pub fn write_resolved(
+        &self,
+        out: &mut dyn FormatterOutput,
+        operand: &ResolvedOperand,
+    ) -> fmt::Result {
+        let options = &self.options;
+        match operand {
+            ResolvedOperand::Immediate(imm) => match options.signed_immediates() {
+                true => options.write_signed_hex(out, TokenKind::Immediate, imm.signed_value()),
+                false => options.write_hex(out, TokenKind::Immediate, imm.value()),
+            },
+            ResolvedOperand::Reg(reg) => options.write_reg(out, *reg),
+            ResolvedOperand::Segment(seg_reg) => {
+                options.write_name(out, TokenKind::Register, &seg_reg.to_string())
+            }
+            ResolvedOperand::Mem(_) => {
+                out.write_token(TokenKind::MemoryBracket, "[")?;
+                write_address(out, options, operand)?;
+                out.write_token(TokenKind::MemoryBracket, "]")
+            }
+            // Without the address of the instruction, we can only print the signed offset
+            ResolvedOperand::Relative(rel) => {
+                if rel.signed_value() >= 0 {
+                    out.write_token(TokenKind::Immediate, "+")?;
+                }
+                options.write_signed_hex(out, TokenKind::Immediate, rel.signed_value())
+            }
+            ResolvedOperand::ToBeDecided => write!(out, "UNKNOWN"),
+        }
+    }
+}
+
+impl Formatter for IntelFormatter {
+    fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        write_prefixes(out, &self.options, inst)?;
+        self.options
+            .write_name(out, TokenKind::Mnemonic, inst.opcode.mnemonic)
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        self.write_operand_of(out, inst.opcode.ident, operand)
+    }
+
+    // Memory operands keep their size and their brackets, like `qword ptr [<main+0x10>]`
+    fn write_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        name: &str,
+    ) -> fmt::Result {
+        if inst.opcode.ident != OpcodeType::Lea {
+            self.write_size(out, operand)?;
+        }
+        let is_mem = matches!(operand.operand, ResolvedOperand::Mem(_));
+        if is_mem {
+            out.write_token(TokenKind::MemoryBracket, "[")?;
+        }
+        out.write_token(TokenKind::Symbol, &format!("<{name}>"))?;
+        if is_mem {
+            out.write_token(TokenKind::MemoryBracket, "]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the address of the memory `operand` as `base+index*scale+disp`, without brackets
+pub fn write_address(
+    out: &mut dyn FormatterOutput,
+    options: &FormatOptions,
+    operand: &ResolvedOperand,
+) -> fmt::Result {
+    let ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) = operand else {
+        return Ok(());
+    };
+    let (base, index) = match eff_addr {
+        EffAddrType::Reg(reg) => (Some(*reg), None),
+        EffAddrType::Sib | EffAddrType::None => (
+            maybe_sib.and_then(|sib| sib.base()),
+            maybe_sib.and_then(|sib| sib.index()),
+        ),
+    };
+    if let Some(base) = base {
+        options.write_reg(out, base)?;
+    }
+    if let Some(index) = index {
+        if base.is_some() {
+            write!(out, "+")?;
+        }
+        options.write_reg(out, index)?;
+        match maybe_sib.map_or(1, |sib| sib.scale()) {
+            1 => {}
+            scale => {
+                write!(out, "*")?;
+                out.write_token(TokenKind::Immediate, &scale.to_string())?
+            }
+        }
+    }
+    if let Some(disp) = maybe_disp {
+        // A displacement added to a register is signed, while a displacement alone is an address
+        match (base.is_some() || index.is_some(), disp.value() < 0) {
+            (true, true) => {
+                write!(out, "-")?;
+                options.write_hex(out, TokenKind::Displacement, disp.value().unsigned_abs())?
+            }
+            (true, false) => {
+                write!(out, "+")?;
+                options.write_hex(out, TokenKind::Displacement, disp.unsigned())?
+            }
+            (false, _) => options.write_hex(out, TokenKind::Displacement, disp.unsigned())?,
+        }
+    }
+    Ok(())
+}
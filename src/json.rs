@@ -0,0 +1,195 @@
+//! Module that writes decoded instructions as JSON, such that other tools can consume them as data
+//! instead of parsing the text of a listing. Each item becomes one object, like
+//! `{"address":16384,"bytes":"4885c0","mnemonic":"test",...}`, and a whole section becomes JSON
+//! Lines, with one object per line.
+use crate::{
+    dis::Decoded,
+    format::Formatter,
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    intel::IntelFormatter,
+    modrm::EffAddrType,
+    opcode::{Access, OpSize},
+    prefix::{Group1, Prefix},
+};
+use core::fmt;
+
+/// Writes the item `decoded` at `address` as a JSON object. An instruction has its mnemonic, its
+/// text in the Intel syntax, its prefixes, its REX bits, its operands and its branch target, while
+/// a byte which could not be decoded has the error it failed with.
+pub fn write_json(out: &mut dyn fmt::Write, decoded: &Decoded, address: u64) -> fmt::Result {
+    write!(out, "{{\"address\":{address},\"bytes\":\"")?;
+    let bytes = match decoded {
+        Decoded::Inst { bytes, .. } => bytes.as_slice(),
+        Decoded::Data { byte, .. } => core::slice::from_ref(byte),
+    };
+    for byte in bytes {
+        write!(out, "{byte:02x}")?;
+    }
+    write!(out, "\"")?;
+
+    match decoded {
+        Decoded::Inst { inst, .. } => write_inst(out, inst, address)?,
+        Decoded::Data { error, .. } => {
+            write!(out, ",\"error\":")?;
+            write_string(out, &error.to_string())?;
+        }
+    }
+    write!(out, "}}")
+}
+
+/// Writes the `items` decoded from bytes loaded at `address` as JSON Lines, one object per line.
+/// The items can come from a `StreamDecoder`, such that they are written as they are decoded.
+/// Addresses wrap around at the end of the address space.
+pub fn write_json_lines<'a>(
+    out: &mut dyn fmt::Write,
+    items: impl IntoIterator<Item = &'a Decoded>,
+    address: u64,
+) -> fmt::Result {
+    for item in items {
+        write_json(out, item, address.wrapping_add(item.offset() as u64))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// Writes the fields of `inst`, which follow its address and its bytes
+fn write_inst(out: &mut dyn fmt::Write, inst: &Instruction, address: u64) -> fmt::Result {
+    write!(out, ",\"mnemonic\":")?;
+    write_string(out, inst.opcode.mnemonic)?;
+    write!(out, ",\"text\":")?;
+    write_string(out, &IntelFormatter::default().format(inst))?;
+
+    write!(out, ",\"prefixes\":[")?;
+    for (idx, prefix) in inst.prefixes().iter().enumerate() {
+        if idx > 0 {
+            write!(out, ",")?;
+        }
+        let name = match prefix {
+            Prefix::Group1(Group1::Lock) => "lock".to_string(),
+            Prefix::Group1(Group1::Rep) => "rep".to_string(),
+            Prefix::Group1(Group1::RepNE) => "repne".to_string(),
+            Prefix::Group2(group) => group.segment().to_string(),
+            Prefix::OpSize => "opsize".to_string(),
+            Prefix::AddrSize => "addrsize".to_string(),
+        };
+        write_string(out, &name)?;
+    }
+    write!(out, "]")?;
+
+    match inst.rex() {
+        Some(rex) => write!(
+            out,
+            ",\"rex\":{{\"w\":{},\"r\":{},\"x\":{},\"b\":{}}}",
+            rex.w(),
+            rex.r(),
+            rex.x(),
+            rex.b()
+        )?,
+        None => write!(out, ",\"rex\":null")?,
+    }
+
+    write!(out, ",\"operands\":[")?;
+    for (idx, operand) in inst.operands.explicit().enumerate() {
+        if idx > 0 {
+            write!(out, ",")?;
+        }
+        write_operand(out, inst, operand)?;
+    }
+    write!(out, "]")?;
+
+    match inst.branch_target(address) {
+        Some(target) => write!(out, ",\"target\":{target}"),
+        None => write!(out, ",\"target\":null"),
+    }
+}
+
+// Writes `operand` as an object, whose fields depend on its type
+fn write_operand(
+    out: &mut dyn fmt::Write,
+    inst: &Instruction,
+    operand: &InstOperand,
+) -> fmt::Result {
+    write!(out, "{{\"type\":")?;
+    match operand.operand {
+        ResolvedOperand::Immediate(imm) => write!(
+            out,
+            "\"imm\",\"value\":{},\"signed\":{}",
+            imm.value(),
+            imm.signed_value()
+        )?,
+        ResolvedOperand::Reg(reg) => {
+            write!(out, "\"reg\",\"reg\":")?;
+            write_string(out, &reg.to_string())?;
+        }
+        ResolvedOperand::Segment(seg) => {
+            write!(out, "\"segment\",\"reg\":")?;
+            write_string(out, &seg.to_string())?;
+        }
+        ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) => {
+            let (base, index) = match eff_addr {
+                EffAddrType::Reg(reg) => (Some(reg), None),
+                EffAddrType::Sib | EffAddrType::None => (
+                    maybe_sib.and_then(|sib| sib.base()),
+                    maybe_sib.and_then(|sib| sib.index()),
+                ),
+            };
+            write!(out, "\"mem\",\"segment\":")?;
+            write_optional(
+                out,
+                inst.segment(&operand.operand).map(|seg| seg.to_string()),
+            )?;
+            write!(out, ",\"base\":")?;
+            write_optional(out, base.map(|reg| reg.to_string()))?;
+            write!(out, ",\"index\":")?;
+            write_optional(out, index.map(|reg| reg.to_string()))?;
+            write!(
+                out,
+                ",\"scale\":{},\"disp\":{}",
+                maybe_sib.map_or(1, |sib| sib.scale()),
+                maybe_disp.map_or(0, |disp| disp.value())
+            )?;
+        }
+        ResolvedOperand::Relative(rel) => write!(out, "\"rel\",\"offset\":{}", rel.signed_value())?,
+        ResolvedOperand::ToBeDecided => write!(out, "\"unknown\"")?,
+    }
+
+    write!(out, ",\"size\":")?;
+    match operand.size {
+        OpSize::U8 | OpSize::I8 => write!(out, "8")?,
+        OpSize::U16 | OpSize::I16 => write!(out, "16")?,
+        OpSize::U32 | OpSize::I32 => write!(out, "32")?,
+        OpSize::U64 | OpSize::I64 => write!(out, "64")?,
+        OpSize::CpuMode => write!(out, "null")?,
+    }
+    let access = match operand.access {
+        Access::Read => "read",
+        Access::Write => "write",
+        Access::ReadWrite => "read_write",
+        Access::Conditional => "conditional",
+    };
+    write!(out, ",\"access\":\"{access}\"}}")
+}
+
+// Writes `value` as a string, or `null` if there is none
+fn write_optional(out: &mut dyn fmt::Write, value: Option<String>) -> fmt::Result {
+    match value {
+        Some(value) => write_string(out, &value),
+        None => write!(out, "null"),
+    }
+}
+
+// Writes `value` as a string, escaping the characters JSON does not allow in strings
+fn write_string(out: &mut dyn fmt::Write, value: &str) -> fmt::Result {
+    write!(out, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\t' => write!(out, "\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+    write!(out, "\"")
+}
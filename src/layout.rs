@@ -0,0 +1,71 @@
+//! Module that describes where each component of an instruction is encoded, such that the
+//! immediate or the displacement of an instruction can be patched in place, or a rip-relative
+//! instruction can be relocated
+use core::ops::Range;
+
+/// The bytes of one component of an encoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    // Offset of the first byte, relative to the start of the instruction
+    pub offset: usize,
+    // Number of bytes of the component
+    pub len: usize,
+}
+
+impl Field {
+    pub fn new(offset: usize, len: usize) -> Self {
+        Self { offset, len }
+    }
+
+    /// Returns the offset right after the last byte of the component
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    /// Returns the range of the component in the bytes of the instruction
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.end()
+    }
+}
+
+/// The offset and length of each component of an encoded instruction. Components which are not
+/// encoded in the instruction are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    // Legacy prefixes, which always start the instruction
+    pub prefixes: Option<Field>,
+    // REX prefix, which comes right before the opcode
+    pub rex: Option<Field>,
+    // The 1, 2 or 3-byte opcode, including a ModRM byte which is part of the opcode
+    pub opcode: Field,
+    pub modrm: Option<Field>,
+    pub sib: Option<Field>,
+    pub disp: Option<Field>,
+    pub imm: Option<Field>,
+}
+
+impl Layout {
+    /// Returns the number of bytes of the instruction
+    pub fn len(&self) -> usize {
+        [
+            self.prefixes,
+            self.rex,
+            Some(self.opcode),
+            self.modrm,
+            self.sib,
+            self.disp,
+            self.imm,
+        ]
+        .iter()
+        .flatten()
+        .map(Field::end)
+        .max()
+        .unwrap_or(0)
+    }
+
+    /// Returns `true` if the instruction has no bytes, which is never the case for a decoded
+    /// instruction
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
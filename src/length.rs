@@ -0,0 +1,128 @@
+//! Module that decodes only the length of an instruction, for hot paths like hooking, patching or
+//! finding instruction boundaries, where the operands of the instruction are not needed
+use crate::{
+    cpu::DecoderConfig,
+    error::{DecodeComponent, DecodeError, DecodeErrorKind},
+    imm::{DispArch, Immediate},
+    inst::{Prefixes, MAX_INST_LEN},
+    modrm::ModRM,
+    opcode::{Operand, OperandEncoding},
+    reader::Reader,
+};
+
+/// Returns the length of the instruction at the cursor of `reader` and moves the cursor right
+/// after it. The prefixes, the opcode and the ModRM and SIB bytes are decoded the same way as in
+/// `Instruction::from_reader_with_config`, but the operands are not resolved and nothing is
+/// allocated.
+pub fn instruction_length(
+    reader: &mut Reader,
+    config: &DecoderConfig,
+) -> Result<usize, DecodeError> {
+    let inst_offset = reader.pos();
+
+    let mut prefixes = Prefixes::default();
+    let opcode = prefixes.read_opcode(reader, inst_offset, config)?;
+
+    if let Some(
+        OperandEncoding::M | OperandEncoding::MI | OperandEncoding::MR | OperandEncoding::RM,
+    ) = opcode.encoding
+    {
+        let modrm_offset = reader.pos();
+        let modrm_byte = read_byte(reader, inst_offset, DecodeComponent::ModRM)?;
+        // Operands which are only memory have no register form
+        let memory_only = opcode
+            .operands
+            .iter()
+            .flatten()
+            .any(|(operand, _)| matches!(operand, Operand::Memory(..)));
+        if memory_only && modrm_byte >> 6 == 0b11 {
+            return Err(DecodeError::from_reader(
+                reader,
+                inst_offset,
+                modrm_offset,
+                DecodeComponent::ModRM,
+                DecodeErrorKind::InvalidModRM(modrm_byte),
+            ));
+        }
+        let mut modrm = ModRM::from_byte_with_arch(
+            modrm_byte,
+            Some(prefixes.addressing_arch(config)),
+            prefixes.rex(),
+        );
+
+        if modrm.1.has_sib() {
+            let sib_byte = read_byte(reader, inst_offset, DecodeComponent::Sib)?;
+            modrm.sib_from_byte(sib_byte, prefixes.rex());
+        }
+
+        if let Some(disp_arch) = modrm.1.displacement() {
+            skip(
+                reader,
+                inst_offset,
+                disp_arch.encoded_len(),
+                DecodeComponent::Displacement,
+            )?;
+        }
+    }
+
+    // Immediates always come last, after any displacement or memory offset
+    let op_size = prefixes.operand_size(config, opcode.ident);
+    for (operand, _) in opcode.operands.iter().flatten() {
+        match operand {
+            Operand::Immediate(op_type) | Operand::Relative(op_type) => {
+                let len = Immediate::encoded_len(op_type.imm_size(op_size));
+                skip(reader, inst_offset, len, DecodeComponent::Immediate)?;
+            }
+            Operand::Offset(_) => {
+                let len = DispArch::from(prefixes.address_size(config)).encoded_len();
+                skip(reader, inst_offset, len, DecodeComponent::Displacement)?;
+            }
+            _ => {}
+        }
+    }
+
+    // The prefixes can push the rest of the instruction past the limit
+    let len = reader.pos() - inst_offset;
+    if len > MAX_INST_LEN {
+        return Err(DecodeError::from_reader(
+            reader,
+            inst_offset,
+            inst_offset,
+            DecodeComponent::Prefix,
+            DecodeErrorKind::TooLong,
+        ));
+    }
+    Ok(len)
+}
+
+// Reads the next byte of the `component`
+fn read_byte(
+    reader: &mut Reader,
+    inst_offset: usize,
+    component: DecodeComponent,
+) -> Result<u8, DecodeError> {
+    let offset = reader.pos();
+    reader
+        .read::<u8>()
+        .map_err(|err| DecodeError::from_reader(reader, inst_offset, offset, component, err))
+}
+
+// Moves the cursor over the next `len` bytes of the `component`
+fn skip(
+    reader: &mut Reader,
+    inst_offset: usize,
+    len: usize,
+    component: DecodeComponent,
+) -> Result<(), DecodeError> {
+    let offset = reader.pos();
+    match reader.read_bytes(len) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(DecodeError::from_reader(
+            reader,
+            inst_offset,
+            offset,
+            component,
+            err,
+        )),
+    }
+}
@@ -0,0 +1,213 @@
+//! Module that writes instructions as MASM source, like `add QWORD PTR [rbp-18h], 1h`
+use crate::{
+    cpu::DecoderConfig,
+    format::{FormatOptions, Formatter, HexStyle},
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    intel::{write_address, IntelFormatter},
+    modrm::EffAddrType,
+    opcode::{OpSize, OpcodeType},
+    prefix::Prefix,
+    source::{
+        has_imm8_form, has_shorter_form, is_rip_relative, relative_offset, source_operands,
+        write_relative, write_source_mnemonic, Assembler,
+    },
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt;
+
+/// Formats instructions as MASM source. Defaults to `h`-suffixed hexadecimal numbers and to signed
+/// immediates.
+#[derive(Debug, Clone, Copy)]
+pub struct MasmFormatter {
+    options: FormatOptions,
+}
+
+impl MasmFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    // Writes `operand`, referring to its address by `label` if it has one
+    fn write_labeled(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        label: Option<&str>,
+    ) -> fmt::Result {
+        let options = &self.options;
+        // MASM takes the address of a label with `OFFSET`
+        if let (ResolvedOperand::Immediate(_), Some(label)) = (operand.operand, label) {
+            out.write_token(TokenKind::Keyword, "OFFSET ")?;
+            return out.write_token(TokenKind::Symbol, label);
+        }
+        match operand.operand {
+            ResolvedOperand::Mem(_) => self.write_mem(out, inst, operand, label),
+            ResolvedOperand::Relative(rel) => {
+                if rel.encoded_size() == OpSize::U8 {
+                    out.write_token(TokenKind::Keyword, "SHORT ")?;
+                }
+                match (label, relative_offset(inst, &operand.operand)) {
+                    (Some(label), _) => out.write_token(TokenKind::Symbol, label),
+                    (None, Some(offset)) => write_relative(out, options, offset),
+                    (None, None) => Ok(()),
+                }
+            }
+            // Registers and immediates are written as in the Intel syntax
+            _ => IntelFormatter::new(*options).write_resolved(out, &operand.operand),
+        }
+    }
+
+    // Writes the memory `operand`, like `QWORD PTR fs:[rax+10h]`
+    fn write_mem(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        label: Option<&str>,
+    ) -> fmt::Result {
+        let options = &self.options;
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, _)) = operand.operand else {
+            return Ok(());
+        };
+
+        if options.memory_size() {
+            match operand.size {
+                OpSize::U8 | OpSize::I8 => out.write_token(TokenKind::Keyword, "BYTE PTR ")?,
+                OpSize::U16 | OpSize::I16 => out.write_token(TokenKind::Keyword, "WORD PTR ")?,
+                OpSize::U32 | OpSize::I32 => out.write_token(TokenKind::Keyword, "DWORD PTR ")?,
+                OpSize::U64 | OpSize::I64 => out.write_token(TokenKind::Keyword, "QWORD PTR ")?,
+                OpSize::CpuMode => {}
+            }
+        }
+
+        // MASM has no name for the instruction pointer, and addresses labels relative to it
+        if is_rip_relative(&operand.operand) {
+            out.write_token(TokenKind::MemoryBracket, "[")?;
+            match (label, relative_offset(inst, &operand.operand)) {
+                (Some(label), _) => out.write_token(TokenKind::Symbol, label)?,
+                (None, Some(offset)) => write_relative(out, options, offset)?,
+                (None, None) => {}
+            }
+            return out.write_token(TokenKind::MemoryBracket, "]");
+        }
+
+        // A number alone in brackets is a constant, unless a segment precedes it
+        let has_reg = match eff_addr {
+            EffAddrType::Reg(_) => true,
+            _ => maybe_sib.is_some_and(|sib| sib.base().is_some() || sib.index().is_some()),
+        };
+        let seg_override = inst
+            .prefixes()
+            .iter()
+            .any(|p| matches!(p, Prefix::Group2(_)));
+        if seg_override || !has_reg {
+            if let Some(seg) = inst.segment(&operand.operand) {
+                options.write_name(out, TokenKind::Register, &seg.to_string())?;
+                write!(out, ":")?;
+            }
+        }
+        out.write_token(TokenKind::MemoryBracket, "[")?;
+        // Only an address without registers has a label
+        match label {
+            Some(label) => out.write_token(TokenKind::Symbol, label)?,
+            None => write_address(out, options, &operand.operand)?,
+        }
+        out.write_token(TokenKind::MemoryBracket, "]")
+    }
+}
+
+impl Default for MasmFormatter {
+    fn default() -> Self {
+        Self::new(
+            FormatOptions::default()
+                .with_hex_style(HexStyle::Suffix)
+                .with_signed_immediates(true),
+        )
+    }
+}
+
+impl Formatter for MasmFormatter {
+    fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        write_source_mnemonic(out, &self.options, inst)
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        self.write_labeled(out, inst, operand, None)
+    }
+
+    fn write_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        name: &str,
+    ) -> fmt::Result {
+        self.write_labeled(out, inst, operand, Some(name))
+    }
+
+    fn operands<'a>(&self, inst: &'a Instruction) -> Vec<&'a InstOperand> {
+        source_operands(inst)
+    }
+}
+
+impl Assembler for MasmFormatter {
+    fn write_header(&self, out: &mut dyn FormatterOutput, _config: &DecoderConfig) -> fmt::Result {
+        writeln!(out, ".code")
+    }
+
+    fn write_footer(&self, out: &mut dyn FormatterOutput) -> fmt::Result {
+        writeln!(out, "END")
+    }
+
+    // MASM has no keywords to keep the size of an immediate or of a branch offset, and it picks
+    // the smallest encoding
+    fn reencodes(&self, inst: &Instruction) -> bool {
+        if has_shorter_form(inst) {
+            return true;
+        }
+        let len = inst.layout().len() as i64;
+        inst.operands
+            .explicit()
+            .any(|operand| match operand.operand {
+                // A `mov` of a 64-bit immediate is `c7 /0` if it fits in 32 bits
+                ResolvedOperand::Immediate(imm) if imm.encoded_size() == OpSize::U64 => {
+                    i32::try_from(imm.signed_value()).is_ok()
+                }
+                ResolvedOperand::Immediate(imm) => {
+                    imm.encoded_size() != OpSize::U8
+                        && has_imm8_form(inst)
+                        && i8::try_from(imm.signed_value()).is_ok()
+                }
+                // A near branch is short if its target is in reach of the 2-byte short form. A
+                // near call has no short form.
+                ResolvedOperand::Relative(rel) => {
+                    rel.encoded_size() != OpSize::U8
+                        && inst.opcode.ident != OpcodeType::CallNear
+                        && i8::try_from(len + rel.signed_value() - 2).is_ok()
+                }
+                _ => false,
+            })
+    }
+
+    fn write_bytes(&self, out: &mut dyn FormatterOutput, bytes: &[u8]) -> fmt::Result {
+        self.options.write_name(out, TokenKind::Keyword, "db ")?;
+        for (idx, byte) in bytes.iter().enumerate() {
+            if idx > 0 {
+                write!(out, ", ")?;
+            }
+            self.options
+                .write_hex(out, TokenKind::Immediate, u64::from(*byte))?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 //! Represents the ModR/M and SIB bytes parsing
 
+use crate::arch::Arch;
 use crate::imm::DispArch;
 use crate::inst::SizedOperand;
 use crate::opcode::{AddrSize, OpSize};
@@ -58,9 +59,6 @@ impl ModRM {
             None => (reg, Addressing::EffAddr32Bit(EffAddr32Bit::from(value))),
         };
 
-        // Get Mod
-        let mod_addr = value >> 6 & 0b11;
-
         Self(Reg::from_byte_with_arch(reg, maybe_arch), addressing)
     }
 
@@ -70,9 +68,9 @@ impl ModRM {
         self.1.rm_reg()
     }
 
-    /// Returns the effective address of the R/M field(from ModRM) if it represents a memory region,
-    /// otherwise `None`
-    pub fn rm_mem(&self) -> EffAddrType {
+    /// Returns the effective address of the R/M field(from ModRM) or `None` if the addressing form
+    /// cannot be represented as an `EffAddrType`
+    pub fn rm_mem(&self) -> Option<EffAddrType> {
         self.1.rm_mem()
     }
 
@@ -80,6 +78,25 @@ impl ModRM {
         self.0
     }
 
+    /// Parses the SIB byte `value` that follows this ModRM byte, or returns `None` if the
+    /// addressing form has no SIB byte. With `mod` = 00, a SIB base of 101 means there is no base
+    /// register and a 32-bit displacement follows instead. This holds even when REX.B extends the
+    /// base to `r13`, so we check the raw bits.
+    pub fn sib_from_byte(&mut self, value: u8, maybe_rex: Option<Rex>) -> Option<Sib> {
+        let mut sib = match self.1 {
+            Addressing::EffAddr32Bit(_) => Sib::Sib32(Sib32::from(value)),
+            Addressing::EffAddr64Bit(_) => Sib::Sib64(Sib64::from_byte_with_rex(value, maybe_rex)),
+            Addressing::EffAddr16Bit(_) => return None,
+        };
+
+        if self.mod_bits() == 0b00 && value & 0b111 == 0b101 {
+            sib.set_base(None);
+            self.1.set_displacement(Some(DispArch::Bit32));
+        }
+
+        Some(sib)
+    }
+
     pub fn mod_bits(&self) -> u8 {
         self.1.mod_bits()
     }
@@ -122,11 +139,7 @@ impl Addressing {
             Self::EffAddr16Bit(_) => return false,
         };
 
-        if let EffAddrType::Sib = eff_addr {
-            return true;
-        } else {
-            return false;
-        }
+        matches!(eff_addr, EffAddrType::Sib)
     }
 
     pub fn mod_bits(&self) -> u8 {
@@ -137,13 +150,36 @@ impl Addressing {
         }
     }
 
-    pub fn rm_mem(&self) -> EffAddrType {
+    pub fn rm_mem(&self) -> Option<EffAddrType> {
         match self {
-            Self::EffAddr32Bit(eff_addr_32bit) => eff_addr_32bit.eff_addr,
-            Self::EffAddr64Bit(eff_addr_64bit) => eff_addr_64bit.eff_addr,
-            Self::EffAddr16Bit(_) => {
-                panic!("Override addressing in 16 bit mode is not implemented")
+            Self::EffAddr32Bit(eff_addr_32bit) => Some(eff_addr_32bit.eff_addr),
+            Self::EffAddr64Bit(eff_addr_64bit) => Some(eff_addr_64bit.eff_addr),
+            Self::EffAddr16Bit(eff_addr_16bit) => {
+                match (eff_addr_16bit.maybe_reg1, eff_addr_16bit.maybe_reg2) {
+                    (Some(reg), None) => Some(EffAddrType::Reg(reg)),
+                    (None, None) => Some(EffAddrType::None),
+                    // The forms which add 2 registers, like [BX+SI], are resolved by `base_index`
+                    (_, _) => Some(EffAddrType::Sib),
+                }
+            }
+        }
+    }
+
+    /// Returns the registers of the 16-bit addressing forms which add a base and an index
+    /// register, like [BX+SI]. They have no SIB byte, but are represented like one, such that
+    /// every base and index register is found in a `Sib`.
+    pub fn base_index(&self) -> Option<Sib> {
+        let Self::EffAddr16Bit(eff_addr_16bit) = self else {
+            return None;
+        };
+        match (eff_addr_16bit.maybe_reg1, eff_addr_16bit.maybe_reg2) {
+            (Some(base), Some(index)) if eff_addr_16bit.mod_addr != 0b11 => {
+                Some(Sib::Sib16(Sib16 {
+                    base: Some(base),
+                    index: Some(index),
+                }))
             }
+            _ => None,
         }
     }
 
@@ -151,29 +187,19 @@ impl Addressing {
     /// otherwise `None`
     pub fn rm_reg(&self) -> Option<Reg> {
         match self {
-            Addressing::EffAddr16Bit(eff_addr_16bit) => {
-                return eff_addr_16bit.maybe_reg1;
-            }
+            Addressing::EffAddr16Bit(eff_addr_16bit) => eff_addr_16bit.maybe_reg1,
             Addressing::EffAddr32Bit(eff_addr_32bit) => match eff_addr_32bit.eff_addr {
-                EffAddrType::Reg(reg) => return Some(reg),
-                _ => return None,
+                EffAddrType::Reg(reg) => Some(reg),
+                _ => None,
             },
             Addressing::EffAddr64Bit(eff_addr_64bit) => match eff_addr_64bit.eff_addr {
-                EffAddrType::Reg(reg) => return Some(reg),
-                _ => return None,
+                EffAddrType::Reg(reg) => Some(reg),
+                _ => None,
             },
         }
     }
 }
 
-// TODO: This should be places in some cpu.rs or arch.rs file
-#[derive(Debug, Clone, Copy)]
-pub enum Arch {
-    Arch16,
-    Arch32,
-    Arch64,
-}
-
 /// Represents an Effective Address using 16-bit mode Addressing
 #[derive(Debug)]
 pub struct EffAddr16Bit {
@@ -287,7 +313,7 @@ pub enum EffAddrType {
     // This means that the base of the effective address is backed by a register
     Reg(Reg),
     // This means that we have to use the SIB(Scale, Base, Index) that follows the ModR/M byte to
-    // get the effective address.
+    // get the effective address, or the base and index registers of a 16-bit form like [BX+SI].
     Sib,
     // No need for a register or a SIB byte
     None,
@@ -416,7 +442,7 @@ impl EffAddr64Bit {
 
         // Since we may be using a REX, we have to extend the r/m byte to the desired register
         if let Some(rex) = maybe_rex {
-            r_m = (rex.b() << 3) | r_m;
+            r_m |= rex.b() << 3;
         }
 
         // Get Mod
@@ -430,7 +456,9 @@ impl EffAddr64Bit {
                     0b0010 => (EffAddrType::Reg(Reg::RDX), None),
                     0b0011 => (EffAddrType::Reg(Reg::RBX), None),
                     0b0100 => (EffAddrType::Sib, None),
-                    0b0101 => (EffAddrType::None, Some(DispArch::Bit32)),
+                    // In 64-bit mode, a 32-bit displacement alone is relative to the next
+                    // instruction, which is also the case when REX.B is set
+                    0b0101 | 0b1101 => (EffAddrType::Reg(Reg::RIP), Some(DispArch::Bit32)),
                     0b0110 => (EffAddrType::Reg(Reg::RSI), None),
                     0b0111 => (EffAddrType::Reg(Reg::RDI), None),
                     0b1000 => (EffAddrType::Reg(Reg::R8), None),
@@ -438,7 +466,6 @@ impl EffAddr64Bit {
                     0b1010 => (EffAddrType::Reg(Reg::R10), None),
                     0b1011 => (EffAddrType::Reg(Reg::R11), None),
                     0b1100 => (EffAddrType::Sib, None),
-                    0b1101 => (EffAddrType::Reg(Reg::R13), Some(DispArch::Bit32)),
                     0b1110 => (EffAddrType::Reg(Reg::R14), None),
                     0b1111 => (EffAddrType::Reg(Reg::R15), None),
                     // Since we know only the low 3 bits can have a value in R/M, this option is
@@ -554,6 +581,8 @@ impl EffAddr64Bit {
 /// base-plus-index and scale-plus-index forms of 32-bit addressing require the SIB byte.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Sib {
+    // The base and index registers of a 16-bit addressing form, which has no SIB byte
+    Sib16(Sib16),
     Sib32(Sib32),
     Sib64(Sib64),
 }
@@ -561,6 +590,7 @@ pub enum Sib {
 impl fmt::Display for Sib {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Sib::Sib16(sib16) => write!(f, "{}", sib16),
             Sib::Sib32(sib32) => write!(f, "{}", sib32),
             Sib::Sib64(sib64) => write!(f, "{}", sib64),
         }
@@ -570,6 +600,7 @@ impl fmt::Display for Sib {
 impl SizedOperand for Sib {
     fn size(&self) -> OpSize {
         match self {
+            Sib::Sib16(_) => OpSize::U16,
             Sib::Sib32(_) => OpSize::U32,
             Sib::Sib64(_) => OpSize::U64,
         }
@@ -580,15 +611,13 @@ impl Sib {
     pub fn convert_with_addrsize(self, addr_size: AddrSize) -> Self {
         let op_size = OpSize::from(addr_size);
         match self {
+            // The 16-bit forms are only used with a 16-bit address size
+            Self::Sib16(_) => self,
             Self::Sib32(sib32) => {
-                let base = match sib32.base {
-                    Some(reg) => Some(reg.convert_with_opsize(&op_size)),
-                    None => None,
-                };
-                let scaled_index = match sib32.scaled_index {
-                    Some(reg) => Some(reg.convert_with_opsize(&op_size)),
-                    None => None,
-                };
+                let base = sib32.base.map(|reg| reg.convert_with_opsize(&op_size));
+                let scaled_index = sib32
+                    .scaled_index
+                    .map(|reg| reg.convert_with_opsize(&op_size));
 
                 Self::Sib32(Sib32 {
                     base,
@@ -597,14 +626,10 @@ impl Sib {
                 })
             }
             Self::Sib64(sib64) => {
-                let base = match sib64.base {
-                    Some(reg) => Some(reg.convert_with_opsize(&op_size)),
-                    None => None,
-                };
-                let scaled_index = match sib64.scaled_index {
-                    Some(reg) => Some(reg.convert_with_opsize(&op_size)),
-                    None => None,
-                };
+                let base = sib64.base.map(|reg| reg.convert_with_opsize(&op_size));
+                let scaled_index = sib64
+                    .scaled_index
+                    .map(|reg| reg.convert_with_opsize(&op_size));
 
                 Self::Sib64(Sib64 {
                     base,
@@ -617,13 +642,33 @@ impl Sib {
 
     pub fn base(&self) -> Option<Reg> {
         match self {
+            Self::Sib16(sib16) => sib16.base,
             Self::Sib32(sib32) => sib32.base,
             Self::Sib64(sib64) => sib64.base,
         }
     }
 
+    pub fn index(&self) -> Option<Reg> {
+        match self {
+            Self::Sib16(sib16) => sib16.index,
+            Self::Sib32(sib32) => sib32.scaled_index,
+            Self::Sib64(sib64) => sib64.scaled_index,
+        }
+    }
+
+    /// Returns the factor the index register is multiplied by
+    pub fn scale(&self) -> u8 {
+        let scale = match self {
+            Self::Sib16(_) => None,
+            Self::Sib32(sib32) => sib32.scale,
+            Self::Sib64(sib64) => sib64.scale,
+        };
+        scale.map_or(1, |scale| scale.0)
+    }
+
     pub fn set_base(&mut self, base: Option<Reg>) {
         match self {
+            Self::Sib16(sib16) => sib16.base = base,
             Self::Sib32(sib32) => sib32.base = base,
             Self::Sib64(sib64) => sib64.base = base,
         };
@@ -631,8 +676,13 @@ impl Sib {
 
     pub fn is_empty(&self) -> bool {
         match self {
-            Self::Sib32(sib32) => sib32.base.is_none() && sib32.scaled_index.is_none() && sib32.scale.is_none(),
-            Self::Sib64(sib64) => sib64.base.is_none() && sib64.scaled_index.is_none() && sib64.scale.is_none(),
+            Self::Sib16(sib16) => sib16.base.is_none() && sib16.index.is_none(),
+            Self::Sib32(sib32) => {
+                sib32.base.is_none() && sib32.scaled_index.is_none() && sib32.scale.is_none()
+            }
+            Self::Sib64(sib64) => {
+                sib64.base.is_none() && sib64.scaled_index.is_none() && sib64.scale.is_none()
+            }
         }
     }
 }
@@ -647,6 +697,28 @@ impl fmt::Display for Scale {
     }
 }
 
+/// Represents the base and index registers of a 16-bit addressing form, like [BX+SI]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Sib16 {
+    base: Option<Reg>,
+    index: Option<Reg>,
+}
+
+impl fmt::Display for Sib16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(base_reg) = self.base {
+            write!(f, "{}", base_reg)?;
+            if self.index.is_some() {
+                write!(f, "+")?;
+            }
+        }
+        if let Some(index_reg) = self.index {
+            write!(f, "{}", index_reg)?;
+        }
+        Ok(())
+    }
+}
+
 /// Represents a 32-bit Sib byte components
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Sib32 {
@@ -660,7 +732,7 @@ impl fmt::Display for Sib32 {
         if let Some(base_reg) = self.base {
             write!(f, "{}", base_reg)?;
             // If we also have a scale, we need to add a plus
-            if let Some(_) = self.scaled_index {
+            if self.scaled_index.is_some() {
                 write!(f, "+")?;
             }
         }
@@ -761,7 +833,7 @@ impl fmt::Display for Sib64 {
         if let Some(base_reg) = self.base {
             write!(f, "{}", base_reg)?;
             // If we also have a scale, we need to add a plus
-            if let Some(_) = self.scaled_index {
+            if self.scaled_index.is_some() {
                 write!(f, "+")?;
             }
         }
@@ -783,8 +855,8 @@ impl Sib64 {
         let mut base = value & 0b111;
 
         if let Some(rex) = maybe_rex {
-            idx = (rex.x() << 3) | idx;
-            base = (rex.b() << 3) | base;
+            idx |= rex.x() << 3;
+            base |= rex.b() << 3;
         }
 
         let base = match base {
@@ -0,0 +1,264 @@
+//! Module that writes instructions as NASM source, like `add qword [rbp-0x18], 0x1`. Where NASM
+//! would pick a shorter encoding than the decoded one, the operands carry the keywords which force
+//! the decoded encoding, such that the source assembles back to the same bytes. The instructions
+//! which no keyword keeps are written as data.
+use crate::{
+    arch::Arch,
+    cpu::DecoderConfig,
+    format::{FormatOptions, Formatter},
+    imm::Displacement,
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    intel::write_address,
+    modrm::EffAddrType,
+    opcode::{OpSize, OpcodeType, OperandEncoding},
+    prefix::Prefix,
+    source::{
+        has_imm8_form, is_rip_relative, relative_offset, source_operands, write_relative,
+        write_source_mnemonic, Assembler,
+    },
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt;
+
+/// Formats instructions as NASM source. Defaults to signed immediates, which NASM accepts for
+/// operands of any size.
+#[derive(Debug, Clone, Copy)]
+pub struct NasmFormatter {
+    options: FormatOptions,
+}
+
+impl NasmFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    // Writes `operand`, referring to its address by `label` if it has one
+    fn write_labeled(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        label: Option<&str>,
+    ) -> fmt::Result {
+        let options = &self.options;
+        if let (ResolvedOperand::Immediate(_), Some(label)) = (operand.operand, label) {
+            return out.write_token(TokenKind::Symbol, label);
+        }
+        match operand.operand {
+            ResolvedOperand::Immediate(imm) => {
+                // An immediate which fits in a byte is encoded as a byte, if the instruction can
+                let fits_in_byte = has_imm8_form(inst) && i8::try_from(imm.signed_value()).is_ok();
+                match (imm.encoded_size(), fits_in_byte) {
+                    (OpSize::U16, true) => {
+                        options.write_name(out, TokenKind::Keyword, "strict word ")?
+                    }
+                    (OpSize::U32, true) => {
+                        options.write_name(out, TokenKind::Keyword, "strict dword ")?
+                    }
+                    // A 64-bit immediate would be shortened to 32 bits
+                    (OpSize::U64, _) => {
+                        options.write_name(out, TokenKind::Keyword, "strict qword ")?
+                    }
+                    _ => {}
+                }
+                match options.signed_immediates() {
+                    true => options.write_signed_hex(out, TokenKind::Immediate, imm.signed_value()),
+                    false => options.write_hex(out, TokenKind::Immediate, imm.value()),
+                }
+            }
+            ResolvedOperand::Reg(reg) => options.write_reg(out, reg),
+            ResolvedOperand::Segment(seg) => {
+                options.write_name(out, TokenKind::Register, &seg.to_string())
+            }
+            ResolvedOperand::Mem(_) => self.write_mem(out, inst, operand, label),
+            ResolvedOperand::Relative(rel) => {
+                match (rel.encoded_size(), inst.opcode.ident) {
+                    (OpSize::U8, _) => options.write_name(out, TokenKind::Keyword, "short ")?,
+                    // A near call has no short form
+                    (_, OpcodeType::CallNear) => {}
+                    _ => options.write_name(out, TokenKind::Keyword, "near ")?,
+                }
+                match (label, relative_offset(inst, &operand.operand)) {
+                    (Some(label), _) => out.write_token(TokenKind::Symbol, label),
+                    (None, Some(offset)) => write_relative(out, options, offset),
+                    (None, None) => Ok(()),
+                }
+            }
+            ResolvedOperand::ToBeDecided => write!(out, "UNKNOWN"),
+        }
+    }
+
+    // Writes the memory `operand`, like `qword [fs:rax+0x10]`
+    fn write_mem(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        label: Option<&str>,
+    ) -> fmt::Result {
+        let options = &self.options;
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) = operand.operand else {
+            return Ok(());
+        };
+
+        if let OpcodeType::CallFar | OpcodeType::JmpFar = inst.opcode.ident {
+            options.write_name(out, TokenKind::Keyword, "far ")?;
+        }
+        // The address computed by `lea` has no size
+        if options.memory_size() && inst.opcode.ident != OpcodeType::Lea {
+            match operand.size {
+                OpSize::U8 | OpSize::I8 => options.write_name(out, TokenKind::Keyword, "byte ")?,
+                OpSize::U16 | OpSize::I16 => {
+                    options.write_name(out, TokenKind::Keyword, "word ")?
+                }
+                OpSize::U32 | OpSize::I32 => {
+                    options.write_name(out, TokenKind::Keyword, "dword ")?
+                }
+                OpSize::U64 | OpSize::I64 => {
+                    options.write_name(out, TokenKind::Keyword, "qword ")?
+                }
+                OpSize::CpuMode => {}
+            }
+        }
+        out.write_token(TokenKind::MemoryBracket, "[")?;
+
+        if is_rip_relative(&operand.operand) {
+            options.write_name(out, TokenKind::Keyword, "rel ")?;
+            match (label, relative_offset(inst, &operand.operand)) {
+                (Some(label), _) => out.write_token(TokenKind::Symbol, label)?,
+                (None, Some(offset)) => write_relative(out, options, offset)?,
+                (None, None) => {}
+            }
+            return out.write_token(TokenKind::MemoryBracket, "]");
+        }
+
+        let seg_override = inst
+            .prefixes()
+            .iter()
+            .any(|p| matches!(p, Prefix::Group2(_)));
+        if let (true, Some(seg)) = (seg_override, inst.segment(&operand.operand)) {
+            options.write_name(out, TokenKind::Register, &seg.to_string())?;
+            write!(out, ":")?;
+        }
+
+        let base = match eff_addr {
+            EffAddrType::Reg(reg) => Some(reg),
+            _ => maybe_sib.and_then(|sib| sib.base()),
+        };
+        let index = maybe_sib.and_then(|sib| sib.index());
+        match (base, index, maybe_disp) {
+            // The 64-bit address of `mov al, [moffs]` is only kept with `qword`
+            (None, None, Some(Displacement::Disp64(_))) => {
+                options.write_name(out, TokenKind::Keyword, "qword ")?
+            }
+            // NASM would encode `[rcx*2]` as `[rcx+rcx]`
+            (None, Some(_), _) => options.write_name(out, TokenKind::Keyword, "nosplit ")?,
+            // NASM picks the shortest displacement, and none if it is 0
+            (Some(_), _, Some(Displacement::Disp8(0))) => {
+                options.write_name(out, TokenKind::Keyword, "byte ")?
+            }
+            (Some(_), _, Some(Displacement::Disp32(disp))) if i8::try_from(disp).is_ok() => {
+                options.write_name(out, TokenKind::Keyword, "dword ")?
+            }
+            _ => {}
+        }
+        // Only an address without registers has a label
+        match label {
+            Some(label) => out.write_token(TokenKind::Symbol, label)?,
+            None => write_address(out, options, &operand.operand)?,
+        }
+        out.write_token(TokenKind::MemoryBracket, "]")
+    }
+}
+
+// Returns whether `inst` operates on 2 registers with the destination in ModRM.reg, while it has
+// another form with the destination in ModRM.rm, like `8b` and `89` for `mov`
+fn has_load_form(inst: &Instruction) -> bool {
+    let reversible = matches!(
+        inst.opcode.ident,
+        OpcodeType::Add
+            | OpcodeType::Or
+            | OpcodeType::Adc
+            | OpcodeType::Sbb
+            | OpcodeType::And
+            | OpcodeType::Sub
+            | OpcodeType::Xor
+            | OpcodeType::Cmp
+            | OpcodeType::Mov
+    );
+    reversible
+        && inst.opcode.encoding == Some(OperandEncoding::RM)
+        && inst
+            .operands
+            .explicit()
+            .all(|operand| matches!(operand.operand, ResolvedOperand::Reg(_)))
+}
+
+impl Default for NasmFormatter {
+    fn default() -> Self {
+        Self::new(FormatOptions::default().with_signed_immediates(true))
+    }
+}
+
+impl Formatter for NasmFormatter {
+    fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    // Operations between 2 registers can encode them either way round, and NASM picks the form
+    // with the destination in ModRM.rm unless it is told to `{load}` it from ModRM.rm
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        if has_load_form(inst) {
+            self.options
+                .write_name(out, TokenKind::Keyword, "{load} ")?;
+        }
+        write_source_mnemonic(out, &self.options, inst)
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        self.write_labeled(out, inst, operand, None)
+    }
+
+    fn write_target(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+        name: &str,
+    ) -> fmt::Result {
+        self.write_labeled(out, inst, operand, Some(name))
+    }
+
+    fn operands<'a>(&self, inst: &'a Instruction) -> Vec<&'a InstOperand> {
+        source_operands(inst)
+    }
+}
+
+impl Assembler for NasmFormatter {
+    fn write_header(&self, out: &mut dyn FormatterOutput, config: &DecoderConfig) -> fmt::Result {
+        let bits = match config.arch() {
+            Arch::Arch16 => 16,
+            Arch::Arch32 => 32,
+            Arch::Arch64 => 64,
+        };
+        self.options.write_name(out, TokenKind::Keyword, "bits ")?;
+        writeln!(out, "{bits}")
+    }
+
+    fn write_bytes(&self, out: &mut dyn FormatterOutput, bytes: &[u8]) -> fmt::Result {
+        self.options.write_name(out, TokenKind::Keyword, "db ")?;
+        for (idx, byte) in bytes.iter().enumerate() {
+            if idx > 0 {
+                write!(out, ", ")?;
+            }
+            self.options
+                .write_hex(out, TokenKind::Immediate, u64::from(*byte))?;
+        }
+        Ok(())
+    }
+}
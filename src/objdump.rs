@@ -0,0 +1,462 @@
+//! Module that writes listings in the layout of `llvm-objdump -d --x86-asm-syntax=intel`, such that
+//! the output of both tools can be diffed line by line. Numbers are written in decimal, memory
+//! operands like `qword ptr [rbp - 8]` and branch targets by address and symbol, like
+//! `0x4016 <.init+0x16>`.
+//!
+//! The listings only match where the opcode table covers the code: an opcode the decoder does not
+//! know is written as a `<unknown>` byte, after which both tools can disagree on where the next
+//! instructions start.
+use crate::{
+    arch::Arch,
+    cpu::DecoderConfig,
+    format::{write_immediate_comment, FormatOptions, Formatter},
+    imm::ImmExtension,
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::{AddrSize, OpSize, OpcodeType, OperandEncoding},
+    prefix::{Group1, Prefix},
+    reader::Reader,
+    reg::RegFamily,
+    source::{is_rip_relative, string_mnemonic},
+    symbol::{SymbolTable, Symbolizer},
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt::{self, Write};
+
+// Column the instructions start at, after the address and the bytes
+const BYTES_WIDTH: usize = 39;
+// Column the comments which follow the operands start at
+const COMMENT_COLUMN: usize = 72;
+
+/// A section of an object file, which takes `size` bytes from `address`
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub address: u64,
+    pub size: u64,
+    // The bytes of a section which holds instructions, or `None` if the section is not
+    // disassembled and only used to name the addresses in it
+    pub code: Option<&'a [u8]>,
+}
+
+impl Section<'_> {
+    // Returns whether `address` is inside the section
+    fn contains(&self, address: u64) -> bool {
+        (self.address..self.address + self.size).contains(&address)
+    }
+}
+
+/// A symbol of an object file, which names `address`
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol<'a> {
+    pub address: u64,
+    pub name: &'a str,
+}
+
+/// Formats instructions the way `llvm-objdump` does in the Intel syntax, like
+/// `mov\trax, qword ptr [rip + 122801]`. Without the address of the instruction, branch targets
+/// are written as if it was at address 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObjdumpFormatter {
+    options: FormatOptions,
+}
+
+impl ObjdumpFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self { options }
+    }
+
+    // Writes the memory `operand`, like `qword ptr fs:[rax + 8*rcx - 16]`
+    fn write_mem(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        let options = &self.options;
+        let ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) = operand.operand else {
+            return Ok(());
+        };
+
+        // The address computed by `lea` has no size
+        if options.memory_size() && inst.opcode.ident != OpcodeType::Lea {
+            match operand.size {
+                OpSize::U8 | OpSize::I8 => {
+                    options.write_name(out, TokenKind::Keyword, "byte ptr ")?
+                }
+                OpSize::U16 | OpSize::I16 => {
+                    options.write_name(out, TokenKind::Keyword, "word ptr ")?
+                }
+                OpSize::U32 | OpSize::I32 => {
+                    options.write_name(out, TokenKind::Keyword, "dword ptr ")?
+                }
+                OpSize::U64 | OpSize::I64 => {
+                    options.write_name(out, TokenKind::Keyword, "qword ptr ")?
+                }
+                OpSize::CpuMode => {}
+            }
+        }
+
+        // The segment is only written when it is not the default one, and for the destination
+        // of string instructions
+        let seg_override = inst
+            .prefixes()
+            .iter()
+            .any(|p| matches!(p, Prefix::Group2(_)));
+        let base = match eff_addr {
+            EffAddrType::Reg(reg) => Some(reg),
+            _ => maybe_sib.and_then(|sib| sib.base()),
+        };
+        let is_string_dest = inst.opcode.ident.is_string()
+            && base.and_then(|reg| reg.family()) == Some(RegFamily::Destination);
+        if seg_override || is_string_dest {
+            if let Some(seg) = inst.segment(&operand.operand) {
+                options.write_name(out, TokenKind::Register, &seg.to_string())?;
+                write!(out, ":")?;
+            }
+        }
+
+        out.write_token(TokenKind::MemoryBracket, "[")?;
+        let index = maybe_sib.and_then(|sib| sib.index());
+        if let Some(base) = base {
+            options.write_reg(out, base)?;
+        }
+        if let Some(index) = index {
+            if base.is_some() {
+                write!(out, " + ")?;
+            }
+            match maybe_sib.map_or(1, |sib| sib.scale()) {
+                1 => {}
+                scale => {
+                    out.write_token(TokenKind::Immediate, &scale.to_string())?;
+                    write!(out, "*")?
+                }
+            }
+            options.write_reg(out, index)?;
+        }
+        let disp = maybe_disp.map_or(0, |disp| disp.value());
+        match (base.is_some() || index.is_some(), disp) {
+            // A displacement alone is an address
+            (false, _) => {
+                let address = maybe_disp.map_or(0, |disp| disp.unsigned());
+                out.write_token(TokenKind::Displacement, &address.to_string())?
+            }
+            (true, 0) => {}
+            (true, disp) => {
+                match disp < 0 {
+                    true => write!(out, " - ")?,
+                    false => write!(out, " + ")?,
+                }
+                let disp = disp.unsigned_abs().to_string();
+                out.write_token(TokenKind::Displacement, &disp)?
+            }
+        }
+        out.write_token(TokenKind::MemoryBracket, "]")
+    }
+}
+
+impl Formatter for ObjdumpFormatter {
+    fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    fn write_mnemonic(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        // `llvm-objdump` writes prefixes in a column of their own, and names them regardless of the
+        // instruction they repeat
+        for prefix in inst.prefixes() {
+            if inst.is_mandatory(prefix) {
+                continue;
+            }
+            let name = match prefix {
+                Prefix::Group1(Group1::Lock) => "lock",
+                Prefix::Group1(Group1::Rep) => "rep",
+                Prefix::Group1(Group1::RepNE) => "repne",
+                _ => continue,
+            };
+            self.options.write_name(out, TokenKind::Prefix, name)?;
+            write!(out, "\t\t")?;
+        }
+        // The forms of `mov` with a 64-bit immediate or a 64-bit offset are `movabs`
+        let movabs = inst.opcode.ident == OpcodeType::Mov
+            && inst
+                .operands
+                .explicit()
+                .any(|operand| match operand.operand {
+                    ResolvedOperand::Immediate(imm) => imm.encoded_size() == OpSize::U64,
+                    _ => {
+                        matches!(
+                            inst.opcode.encoding,
+                            Some(OperandEncoding::FD | OperandEncoding::TD)
+                        ) && inst.address_size() == AddrSize::Addr64Bit
+                    }
+                });
+        match movabs {
+            true => self.options.write_name(out, TokenKind::Mnemonic, "movabs"),
+            false => self
+                .options
+                .write_name(out, TokenKind::Mnemonic, &string_mnemonic(inst)),
+        }
+    }
+
+    fn write_operand(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        operand: &InstOperand,
+    ) -> fmt::Result {
+        let options = &self.options;
+        match operand.operand {
+            // Byte, 64-bit and sign-extended immediates are written signed, like the word
+            // immediates of `ret` and `enter`. The immediates of the operand size are unsigned.
+            ResolvedOperand::Immediate(imm) => {
+                let signed = matches!(imm.encoded_size(), OpSize::U8 | OpSize::U64)
+                    || matches!(imm.extension(), ImmExtension::Sign)
+                    || matches!(inst.opcode.ident, OpcodeType::RetNear | OpcodeType::Enter);
+                match signed {
+                    true => out.write_token(TokenKind::Immediate, &imm.signed_value().to_string()),
+                    false => out.write_token(TokenKind::Immediate, &imm.value().to_string()),
+                }
+            }
+            ResolvedOperand::Reg(reg) => options.write_reg(out, reg),
+            ResolvedOperand::Segment(seg) => {
+                options.write_name(out, TokenKind::Register, &seg.to_string())
+            }
+            ResolvedOperand::Mem(_) => self.write_mem(out, inst, operand),
+            ResolvedOperand::Relative(_) => match inst.branch_target(0) {
+                Some(target) => out.write_token(TokenKind::Immediate, &format!("0x{target:x}")),
+                None => Ok(()),
+            },
+            ResolvedOperand::ToBeDecided => write!(out, "<unknown>"),
+        }
+    }
+
+    // The targets of relative branches are written by address, followed by their name, if any
+    fn write_at(
+        &self,
+        out: &mut dyn FormatterOutput,
+        inst: &Instruction,
+        address: u64,
+        symbolizer: &dyn Symbolizer,
+    ) -> fmt::Result {
+        self.write_mnemonic(out, inst)?;
+        let operands = self.operands(inst);
+        if !operands.is_empty() {
+            write!(out, "\t")?;
+        }
+        for (idx, operand) in operands.into_iter().enumerate() {
+            if idx > 0 {
+                write!(out, ", ")?;
+            }
+            match (operand.operand, inst.branch_target(address)) {
+                (ResolvedOperand::Relative(_), Some(target)) => {
+                    out.write_token(TokenKind::Immediate, &format!("0x{target:x}"))?;
+                    if let Some(name) = symbolizer.symbolize(target) {
+                        write!(out, " ")?;
+                        out.write_token(TokenKind::Symbol, &format!("<{name}>"))?;
+                    }
+                }
+                _ => self.write_operand(out, inst, operand)?,
+            }
+        }
+        write_immediate_comment(out, self, inst, None)
+    }
+
+    // Like the comments `llvm-objdump` writes, after a tab
+    fn write_comment(&self, out: &mut dyn FormatterOutput, comment: &str) -> fmt::Result {
+        write!(out, "\t")?;
+        out.write_token(TokenKind::Comment, &format!("# {comment}"))
+    }
+
+    fn write(&self, out: &mut dyn FormatterOutput, inst: &Instruction) -> fmt::Result {
+        self.write_at(out, inst, 0, &SymbolTable::new())
+    }
+}
+
+/// Writes the listing `llvm-objdump` gives for the file at `path`, whose format is named like
+/// `elf64-x86-64`. Each section which holds code is decoded and written one instruction per line,
+/// after the name of each of `symbols` which starts at the address of the instruction. The
+/// addresses instructions refer to are named after the closest symbol before them in their
+/// section, or after the start of their section once it is disassembled.
+pub fn write_listing(
+    out: &mut dyn fmt::Write,
+    path: &str,
+    format: &str,
+    sections: &[Section],
+    symbols: &[Symbol],
+    config: &DecoderConfig,
+) -> fmt::Result {
+    writeln!(out)?;
+    writeln!(out, "{path}:\tfile format {format}")?;
+
+    // `llvm-objdump` only names addresses after the start of the sections it went through
+    let mut known = symbols.to_vec();
+    for section in sections {
+        let Some(code) = section.code else {
+            continue;
+        };
+        if !symbols
+            .iter()
+            .any(|symbol| symbol.address == section.address)
+        {
+            known.push(Symbol {
+                address: section.address,
+                name: section.name,
+            });
+        }
+        write_section(out, section, code, sections, &known, config)?;
+    }
+    Ok(())
+}
+
+// Writes the instructions decoded from the `code` of `section`, naming addresses after `symbols`
+fn write_section(
+    out: &mut dyn fmt::Write,
+    section: &Section,
+    code: &[u8],
+    sections: &[Section],
+    symbols: &[Symbol],
+    config: &DecoderConfig,
+) -> fmt::Result {
+    // Decoding starts over at each symbol, such that a symbol always starts an instruction
+    let mut starts: Vec<usize> = symbols
+        .iter()
+        .filter(|symbol| section.contains(symbol.address))
+        .map(|symbol| (symbol.address - section.address) as usize)
+        .filter(|start| *start < code.len())
+        .chain([0])
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    let mut lines = vec![];
+    for (idx, start) in starts.iter().enumerate() {
+        let end = starts.get(idx + 1).copied().unwrap_or(code.len());
+        let mut reader = Reader::from_vec(code[*start..end].to_vec());
+        while reader.bytes_unread() > 0 {
+            let offset = reader.pos();
+            if let Some((len, name)) = split_prefixes(&code[start + offset..end], config) {
+                lines.push((start + offset, len, Line::Named(name)));
+                reader.seek(offset + len).map_err(|_| fmt::Error)?;
+                continue;
+            }
+            match Instruction::from_reader_with_config(&mut reader, config) {
+                Ok(inst) => lines.push((
+                    start + offset,
+                    reader.pos() - offset,
+                    Line::Inst(Box::new(inst)),
+                )),
+                // Bytes which cannot be decoded are skipped, like `Recovery::SkipByte` does
+                Err(_) => {
+                    lines.push((start + offset, 1, Line::Named("<unknown>")));
+                    reader.seek(offset + 1).map_err(|_| fmt::Error)?;
+                }
+            }
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Disassembly of section {}:", section.name)?;
+
+    let formatter = ObjdumpFormatter::default();
+    let symbolize = |target: u64| symbolize(sections, symbols, target);
+    for (offset, len, item) in &lines {
+        let address = section.address + *offset as u64;
+        for symbol in symbols.iter().filter(|symbol| symbol.address == address) {
+            writeln!(out)?;
+            writeln!(out, "{address:016x} <{}>:", symbol.name)?;
+        }
+
+        let bytes = &code[*offset..offset + len];
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        let line = format!("{address:>8x}: {}", hex.join(" "));
+        // The bytes of long instructions push the instruction to the next tab stop
+        let width = match line.len() {
+            len if len <= BYTES_WIDTH => BYTES_WIDTH,
+            len => BYTES_WIDTH + (len - BYTES_WIDTH).div_ceil(8) * 8,
+        };
+        let mut line = format!("{line:<width$}\t");
+
+        match item {
+            Line::Inst(inst) => {
+                formatter.write_at(&mut line, inst, address, &symbolize)?;
+                let rip_target = inst
+                    .operands
+                    .explicit()
+                    .filter(|operand| is_rip_relative(&operand.operand))
+                    .find_map(|operand| inst.target(&operand.operand, address));
+                if let Some(target) = rip_target {
+                    let padding = COMMENT_COLUMN.saturating_sub(expanded_len(&line)).max(1);
+                    write!(line, "{:padding$}# 0x{target:x}", "")?;
+                    if let Some(name) = symbolize(target) {
+                        write!(line, " <{name}>")?;
+                    }
+                }
+            }
+            Line::Named(name) => line.push_str(name),
+        }
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+// A line of a listing, which holds an instruction, or bytes written by name like `<unknown>`
+enum Line {
+    Inst(Box<Instruction>),
+    Named(&'static str),
+}
+
+// Returns the length and the name of the prefixes at the start of `code` which `llvm-objdump`
+// writes on a line of their own, before it decodes the instruction after them: a leading `lock`, a
+// REX prefix followed by a legacy prefix, which is named after the legacy prefix, and two REX
+// prefixes, which are `rex64` if the second one sets REX.W
+fn split_prefixes(code: &[u8], config: &DecoderConfig) -> Option<(usize, &'static str)> {
+    let is_rex = |byte: &u8| config.arch() == Arch::Arch64 && byte & 0xf0 == 0x40;
+    match code {
+        [0xf0, ..] => Some((1, "lock")),
+        [rex, byte, ..] if is_rex(rex) => {
+            let name = match byte {
+                0xf0 => "lock",
+                0xf2 => "repne",
+                0xf3 => "rep",
+                0x66 => "data16",
+                0x67 => "addr32",
+                0x26 => "es",
+                0x2e => "cs",
+                0x36 => "ss",
+                0x3e => "ds",
+                0x64 => "fs",
+                0x65 => "gs",
+                byte if is_rex(byte) && byte & 0x08 != 0 => "rex64",
+                byte if is_rex(byte) => "<unknown>",
+                _ => return None,
+            };
+            Some((2, name))
+        }
+        _ => None,
+    }
+}
+
+// Returns the name of `target`, relative to the closest of `symbols` before it in its section
+fn symbolize(sections: &[Section], symbols: &[Symbol], target: u64) -> Option<String> {
+    // Addresses outside of the sections are not named
+    let section = sections.iter().find(|section| section.contains(target))?;
+    let symbol = symbols
+        .iter()
+        .filter(|symbol| (section.address..=target).contains(&symbol.address))
+        // The first of the symbols at the same address wins
+        .fold(None::<&Symbol>, |closest, symbol| match closest {
+            Some(closest) if closest.address >= symbol.address => Some(closest),
+            _ => Some(symbol),
+        })?;
+    match target - symbol.address {
+        0 => Some(symbol.name.to_string()),
+        offset => Some(format!("{}+0x{offset:x}", symbol.name)),
+    }
+}
+
+// Returns the length of `line` once its tabs are expanded to multiples of 8 columns
+fn expanded_len(line: &str) -> usize {
+    line.chars().fold(0, |column, c| match c {
+        '\t' => (column / 8 + 1) * 8,
+        _ => column + 1,
+    })
+}
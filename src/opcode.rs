@@ -1,10 +1,13 @@
 //! Module that takes care of parsing the Opcode field in an instruction.
 use crate::{
-    modrm::Arch,
-    prefix::{Group1, Prefix},
+    arch::Arch,
+    cond::Condition,
+    cpu::Extension,
+    prefix::Prefix,
     reader::{Reader, ReaderError},
-    reg::{Accumulator, Gpr, Reg, RegFamily, SegmentRegister},
+    reg::{Reg, RegFamily, SegmentRegister},
     rex::Rex,
+    table::{self, MandatoryPrefix, ModRMSelector, OpcodeEntry, OpcodeMap, OperandSpec},
 };
 
 /// Represents a primary opcode in an x86_64 Architecture. The primary opcode can be 1, 2 or even
@@ -40,6 +43,10 @@ pub enum OpcodeType {
     Dec,
     CallNear,
     CallFar,
+    // Near return, which pops the return address and optionally releases more bytes of the stack
+    RetNear,
+    // Create a stack frame for a procedure
+    Enter,
     JmpNear,
     JmpFar,
     Push,
@@ -50,28 +57,105 @@ pub enum OpcodeType {
     Daa,
     // A bitwise XOR between 2 operands
     Xor,
+    // No operation
+    Nop,
+    // Exchange the values of 2 operands
+    Xchg,
+    Mov,
+    // Move with zero extension
+    Movzx,
+    // Move with sign extension
+    Movsx,
+    // A bitwise AND between 2 operands, which only sets the flags
+    Test,
+    Not,
+    // Two's complement negation
+    Neg,
+    // Unsigned multiply
+    Mul,
+    // Signed multiply
+    Imul,
+    // Unsigned divide
+    Div,
+    // Signed divide
+    Idiv,
     // The opcode alone is not enough and it needs an Extension from a ModRM field
     NeedsModRMExtension(u8),
     // Terminate an indirect branch in 32 bit and compatibility mode.
     EndBr32,
     // Terminate an indirect branch in 64 bit mode.
     EndBr64,
-    // Specifies and unknown opcode
-    Unknown,
+    // Move data from string to string
+    Movs,
+    // Compare string operands
+    Cmps,
+    // Store string
+    Stos,
+    // Load string
+    Lods,
+    // Scan string
+    Scas,
+    // Jump short or near if the condition holds
+    Jcc(Condition),
+    // Set a byte to 1 if the condition holds and to 0 otherwise
+    Setcc(Condition),
+    // Move if the condition holds
+    Cmovcc(Condition),
+    // Fast call to the operating system, which takes the number of the call in rAX
+    Syscall,
+    // Fast return from the operating system to user mode
+    Sysret,
+    // Halt the processor until the next interrupt
+    Hlt,
+    // Clear the interrupt flag, which masks the maskable interrupts
+    Cli,
+    // Set the interrupt flag
+    Sti,
+    // Clear the task-switched flag in CR0
+    Clts,
+    // Invalidate the caches, without writing them back to memory
+    Invd,
+    // Write back and invalidate the caches
+    Wbinvd,
+}
+
+impl OpcodeType {
+    /// Returns whether the instruction is a string instruction, which addresses its memory
+    /// operands with rSI and rDI, advances them after each iteration and can be repeated with a
+    /// REP prefix
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            Self::Movs | Self::Cmps | Self::Stos | Self::Lods | Self::Scas
+        )
+    }
 }
 
 #[macro_export]
 macro_rules! stringify_opcode_type {
     ($opcode_type:expr) => {
         format!("{:?}", $opcode_type).to_lowercase()
-    }
+    };
 }
 
 #[derive(Debug)]
 pub struct Opcode {
     pub ident: OpcodeType,
-    pub operands: [Option<Operand>; 4],
+    // The mnemonic of the instruction, as written in the opcode table
+    pub mnemonic: &'static str,
+    pub operands: [Option<(Operand, Access)>; 4],
+    // The operands as written in the opcode table, like `Ev`
+    pub specs: [Option<OperandSpec>; 4],
+    // Registers the instruction accesses without encoding them, like the stack pointer of `push`
+    pub implicit: [Option<(Operand, Access)>; 4],
     pub encoding: Option<OperandEncoding>,
+    // The ISA extension the instruction belongs to, if it is not part of the base instruction set
+    pub extension: Option<Extension>,
+    // The prefix which selected the instruction as part of its opcode, rather than as a legacy
+    // prefix, like the 0xF3 of `endbr64`
+    pub prefix: MandatoryPrefix,
+    // The forms of an opcode group, one of which is selected by the ModRM.reg field
+    group: &'static [OpcodeEntry],
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -83,8 +167,17 @@ pub enum AddressingMethod {
     G,
     // Immediate data: the operand value is encoded in subsequent bytes of the instruction.
     I,
+    // The instruction contains a relative offset to be added to the instruction pointer
+    J,
     // The r/m part of the ModRM byte, represents only a memory address
     M,
+    // There is no ModR/M byte, and the offset of the memory operand follows the opcode. The offset
+    // has the size of the address-size attribute.
+    O,
+    // Memory addressed by the DS:rSI register pair, used by the string instructions
+    X,
+    // Memory addressed by the ES:rDI register pair, used by the string instructions
+    Y,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -93,17 +186,42 @@ pub enum OperandType {
     B,
     // Doubleword, regardless of operand-size attribute.
     D,
+    // 32-bit, 48-bit, or 80-bit far pointer, depending on operand-size attribute
+    P,
     // Word, doubleword or quadword (in 64-bit mode), depending on operand-size attribute
     V,
+    // Word, regardless of operand-size attribute.
+    W,
     // Word for 16-bit operand-size or doubleword for 32 or 64-bit operand-size.
     Z,
 }
 
+impl OperandType {
+    /// Returns the size of an immediate of this type, once the operand-size attribute of the
+    /// instruction is `op_size`. A `z` immediate is at most 32-bit and it is sign-extended to a
+    /// 64-bit operand, while a `v` immediate is 64-bit with a 64-bit operand.
+    pub fn imm_size(self, op_size: OpSize) -> OpSize {
+        match self {
+            Self::B => OpSize::U8,
+            Self::W => OpSize::U16,
+            Self::D => OpSize::U32,
+            // Far pointers are only read from memory, so they are never immediates
+            Self::P | Self::V => op_size,
+            Self::Z => match op_size {
+                OpSize::U64 | OpSize::I64 => OpSize::U32,
+                op_size => op_size,
+            },
+        }
+    }
+}
+
 /// Describes the different encodings for the instruction operands
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OperandEncoding {
     // Op1 = AL/AX/EAX/RAX, Op2 = imm8/16/32
     I,
+    // Op1 = an offset relative to the next instruction
+    D,
     // Op1 = ModRM:r/m(r)
     M,
     // Op1 is encoded in the lower 3 bits of the opcode
@@ -112,12 +230,28 @@ pub enum OperandEncoding {
     MI,
     // Op1 = ModRM:r/m(r, w), Op2 = ModRM:reg(r)
     MR,
+    // Op1 is encoded in the lower 3 bits of the opcode, Op2 = imm8/16/32/64
+    OI,
+    // Op1 = AL/AX/EAX/RAX, Op2 = a memory offset that follows the opcode
+    FD,
+    // Op1 = a memory offset that follows the opcode, Op2 = AL/AX/EAX/RAX
+    TD,
     // Op1 = ModRM:reg(r, w), Op2 = ModRM:r/m(r)
     RM,
     // Zero operators
     ZO,
 }
 
+/// Describes how an instruction accesses one of its operands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+    // The operand is written only if a condition holds, like the destination of CMOVcc
+    Conditional,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct RegFieldExt(u8);
@@ -141,10 +275,11 @@ impl TryFrom<u8> for RegFieldExt {
 /// The operator size of the opcode is determined by 2 characteristics:
 /// - The CPU Mode
 /// - The OperandSize override prefix, which alternates the state between the 16-bit and the 32-bit
-/// states of the CPU
+///   states of the CPU
 /// - The Opcode identifier itself.
+///
 /// The current module, only controls the last one and the first 2 have to be addressed in the
-/// `Intruction` module
+/// `Intruction` module.
 /// Pay attention to the variants as their order matter, since they derive the `PartialOrd` trait
 #[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy)]
 pub enum OpSize {
@@ -189,24 +324,31 @@ impl From<Arch> for OpSize {
 
 impl OpSize {
     pub fn from_cpu_opcode(value: Arch, opcode_ident: OpcodeType) -> Self {
-        // We have certain instructions which have the default operation size different from the
-        // majority
+        // Near branches and the instructions that implicitly reference the stack pointer default
+        // to a 64-bit operand size in 64-bit mode. In both 32-bit and 64-bit mode, the default
+        // operand size of all the other instructions is 32-bit.
         match opcode_ident {
-            OpcodeType::Add
-            | OpcodeType::Adc
-            | OpcodeType::And
-            | OpcodeType::Xor
-            | OpcodeType::Or
-            | OpcodeType::Sbb
-            | OpcodeType::Sub
-            | OpcodeType::Cmp => {
-                match value {
-                    Arch::Arch16 => Self::U16,
-                    // In both 32-bit and 64-bit mode, the default operand size, is 32-bit,
-                    Arch::Arch32 | Arch::Arch64 => Self::U32,
-                }
-            }
-            _ => OpSize::from(value)
+            OpcodeType::Push
+            | OpcodeType::Pop
+            | OpcodeType::CallNear
+            | OpcodeType::RetNear
+            | OpcodeType::Enter
+            | OpcodeType::JmpNear
+            | OpcodeType::Jcc(_) => OpSize::from(value),
+            _ => match value {
+                Arch::Arch16 => Self::U16,
+                Arch::Arch32 | Arch::Arch64 => Self::U32,
+            },
+        }
+    }
+
+    /// Returns the size of an operand of this size, once the operand-size attribute of the
+    /// instruction is `op_size`. Only operands sized by the CPU mode follow the attribute, while
+    /// the others have a fixed size.
+    pub fn with_attribute(self, op_size: OpSize) -> OpSize {
+        match self {
+            OpSize::CpuMode => op_size,
+            _ => self,
         }
     }
 }
@@ -229,14 +371,21 @@ pub struct OperandList(Operand, Operand, Operand, Operand);
 pub enum Operand {
     // Represents a register or a memory operand found in the R/M field of ModR/M
     ModRM(OpSize, AddrSize),
+    // Represents a memory operand found in the R/M field of ModR/M, which cannot be a register
+    Memory(OpSize, AddrSize),
     // Represents a register from the `reg` part of the ModRM field
     ModReg(OpSize),
     // The operand is embedded in the opcode
     Opcode(OpSize),
-    // There is an Immediate integer following the opcode that represents the operand
-    Immediate(OpSize),
+    // There is an Immediate integer following the opcode that represents the operand. Its size is
+    // given by the operand type and the operand-size attribute.
+    Immediate(OperandType),
     // There is a Signed Immediate integer following the opcode that represents the operand
     SignedImmediate(OpSize),
+    // An offset relative to the next instruction, which follows the opcode like an immediate
+    Relative(OperandType),
+    // A memory operand whose offset follows the opcode, like the `moffs` of `mov al, [moffs8]`
+    Offset(OpSize),
     // The operand is a specific register or a set of registers
     Reg(Reg),
     // The operand is a family of registers and reffers to General Purpose Registers
@@ -245,36 +394,44 @@ pub enum Operand {
     RegInOpcode(u8),
     // The operand represents a segment selector
     Segment(SegmentRegister),
+    // The operand is the memory addressed by the rSI or rDI register of a string instruction
+    String(RegFamily, OpSize),
 }
 
 impl Operand {
     pub fn from_map(addr_meth: AddressingMethod, op_type: OperandType, arch: Arch) -> Self {
         let op_size = match op_type {
             OperandType::B => OpSize::U8,
-            OperandType::V => OpSize::CpuMode,
-            OperandType::Z => match arch {
-                Arch::Arch16 => OpSize::U16,
-                Arch::Arch32 | Arch::Arch64 => OpSize::U32,
-            },
+            // The size of a far pointer is given by the size of its offset
+            OperandType::P | OperandType::V => OpSize::CpuMode,
+            // The size is capped at 32-bit for immediates, see `OperandType::imm_size`
+            OperandType::Z => OpSize::CpuMode,
             OperandType::D => OpSize::U32,
+            OperandType::W => OpSize::U16,
         };
 
         match addr_meth {
             AddressingMethod::E => Operand::ModRM(op_size, AddrSize::from(arch)),
-            AddressingMethod::M => Operand::ModRM(op_size, AddrSize::from(arch)),
+            AddressingMethod::M => Operand::Memory(op_size, AddrSize::from(arch)),
             AddressingMethod::G => Operand::ModReg(op_size),
-            AddressingMethod::I => Operand::Immediate(op_size),
+            AddressingMethod::I => Operand::Immediate(op_type),
+            AddressingMethod::J => Operand::Relative(op_type),
+            AddressingMethod::O => Operand::Offset(op_size),
+            AddressingMethod::X => Operand::String(RegFamily::Source, op_size),
+            AddressingMethod::Y => Operand::String(RegFamily::Destination, op_size),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum OpcodeError {
     ReaderError(ReaderError),
     InvalidPrefix(Prefix),
     InexistentPrefix,
     InvalidOpcode(u8),
     Invalid3ByteOpcode(u8, u8, u8),
+    // The `reg` field of the ModRM byte does not select a valid instruction for the opcode group
+    InvalidExtension(u8, u8),
 }
 
 impl From<ReaderError> for OpcodeError {
@@ -293,432 +450,15 @@ impl Opcode {
         Self::from_byte_with_arch(byte, arch)
     }
 
-    /// Parse the next `Opcode` from the `reader`, given the prefix. We need to pass the `reader`
-    /// to this function, since we do not know if the opcode is 1, 2 or 3 bytes
+    /// Parse `byte` as a prefix or as an opcode from the one-byte opcode map
     pub fn from_byte_with_arch(byte: u8, arch: Arch) -> Result<Self, OpcodeError> {
-        // We first try and parse the byte for a prefix
-        let maybe_prefix = Prefix::from_byte(byte);
-
         // If we do get a prefix, we return and it is the caller job, to do something with it
-        if let Some(prefix) = maybe_prefix {
-            return Ok(Opcode {
-                ident: OpcodeType::Prefix(prefix),
-                operands: [None, None, None, None],
-                encoding: None,
-            });
+        if let Some(opcode) = Self::from_prefix_byte(byte, arch) {
+            return Ok(opcode);
         }
 
-        // If it is not a prefix, we still need to check for a REX prefix
-        let maybe_rex = Rex::from_byte(byte);
-
-        // If we do get a REX prefix, we return and it is the caller's job to call opcode parsing
-        // again for the next byte
-        if let Some(rex) = maybe_rex {
-            return Ok(Opcode {
-                ident: OpcodeType::Rex(rex),
-                operands: [None, None, None, None],
-                encoding: None,
-            });
-        }
-
-        // This(soon to be gigantic match) will check the byte for the appropriate instruction.
-        // It is the job of this match to make sure we propagate the information upwards, that the
-        // calling function needs, in order to parse the rest of the bytes
-        match byte {
-            // ADD opcodes
-            0x00 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            0x01 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            0x02 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            0x03 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            0x04 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Reg(Reg::AL));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            0x05 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::RegFamily(RegFamily::Accumulator));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::Z, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Add,
-                    operands,
-                    encoding,
-                })
-            }
-            // Push Extra Selector
-            0x06 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::ES));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            // Pop Extra Selector
-            0x07 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::ES));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Pop,
-                    operands,
-                    encoding,
-                })
-            }
-            // Push Code Selector
-            0x0e => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::CS));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            // ADC opcodes
-            0x10 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            0x11 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            0x12 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            0x13 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            0x14 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Reg(Reg::AL));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            0x15 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::RegFamily(RegFamily::Accumulator));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::Z, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Adc,
-                    operands,
-                    encoding,
-                })
-            }
-            // Push Stack Selector
-            0x16 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::SS));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            // Pop Stack Selector
-            0x17 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::SS));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Pop,
-                    operands,
-                    encoding,
-                })
-            }
-            // Push Data Selector
-            0x1e => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::DS));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            // Pop Data Selector
-            0x1f => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Segment(SegmentRegister::DS));
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Pop,
-                    operands,
-                    encoding,
-                })
-            }
-            // AND opcodes
-            0x20 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x21 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x22 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x23 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::G, OperandType::V, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                let encoding = Some(OperandEncoding::RM);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x24 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::Reg(Reg::AL));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x25 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::RegFamily(RegFamily::Accumulator));
-                operands[1] = Some(Operand::from_map(AddressingMethod::I, OperandType::Z, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::And,
-                    operands,
-                    encoding,
-                })
-            }
-            0x27 => {
-                let mut operands = [None, None, None, None];
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Daa,
-                    operands,
-                    encoding,
-                })
-            }
-            0x37 => {
-                let mut operands = [None, None, None, None];
-                let encoding = Some(OperandEncoding::ZO);
-                Ok(Opcode {
-                    ident: OpcodeType::Aaa,
-                    operands,
-                    encoding,
-                })
-            }
-            // XOR opcodes
-            0x30 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                operands[1] = Some(Operand::from_map(AddressingMethod::G, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::MR);
-                Ok(Opcode {
-                    ident: OpcodeType::Xor,
-                    operands,
-                    encoding,
-                })
-            }
-            0x31 => Ok(Opcode {
-                ident: OpcodeType::Xor,
-                operands: [
-                    Some(Operand::ModRM(OpSize::CpuMode, AddrSize::from(arch))),
-                    Some(Operand::ModReg(OpSize::CpuMode)),
-                    None,
-                    None,
-                ],
-                encoding: Some(OperandEncoding::MR),
-            }),
-            0x34 => Ok(Opcode {
-                ident: OpcodeType::Xor,
-                operands: [
-                    Some(Operand::Reg(Accumulator::Reg8BitLo)),
-                    Some(Operand::Immediate(OpSize::U8)),
-                    None,
-                    None,
-                ],
-                encoding: Some(OperandEncoding::I),
-            }),
-            0x35 => Ok(Opcode {
-                ident: OpcodeType::Xor,
-                operands: [
-                    Some(Operand::RegFamily(RegFamily::Accumulator)),
-                    Some(Operand::Immediate(OpSize::U32)),
-                    None,
-                    None,
-                ],
-                encoding: Some(OperandEncoding::I),
-            }),
-            // Push Opcode with general register
-            0x50 | 0x51 | 0x52 | 0x53 | 0x54 | 0x55 | 0x56 | 0x57 => Ok(Opcode {
-                ident: OpcodeType::Push,
-                operands: [Some(Operand::RegInOpcode(byte)), None, None, None],
-                encoding: Some(OperandEncoding::O),
-            }),
-            // Pop Opcode with general register
-            0x58 | 0x59 | 0x5A | 0x5B | 0x5C | 0x5D | 0x5E | 0x5F => Ok(Opcode {
-                ident: OpcodeType::Pop,
-                operands: [Some(Operand::RegInOpcode(byte)), None, None, None],
-                encoding: Some(OperandEncoding::O),
-            }),
-            // Push Opcode for immediates
-            0x68 => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::I, OperandType::Z, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            0x6A => {
-                let mut operands = [None, None, None, None];
-                operands[0] = Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                let encoding = Some(OperandEncoding::I);
-                Ok(Opcode {
-                    ident: OpcodeType::Push,
-                    operands,
-                    encoding,
-                })
-            }
-            // Immediate Group 1, which needs extension from ModRM in order to get the opcode
-            0x80 | 0x81 | 0x82 | 0x83 | 0x8F | 0xFF => Ok(Opcode {
-                ident: OpcodeType::NeedsModRMExtension(byte),
-                operands: [None, None, None, None],
-                encoding: None,
-            }),
-            // LEA
-            0x8D => Ok(Opcode {
-                ident: OpcodeType::Lea,
-                operands: [
-                    Some(Operand::ModReg(OpSize::CpuMode)),
-                    Some(Operand::ModRM(OpSize::CpuMode, AddrSize::from(arch))),
-                    None,
-                    None,
-                ],
-                encoding: Some(OperandEncoding::RM),
-            }),
-            _ => Ok(Opcode {
-                ident: OpcodeType::Unknown,
-                operands: [None, None, None, None],
-                encoding: None,
-            }),
-        }
+        let entries = table::lookup(OpcodeMap::OneByte, byte, MandatoryPrefix::None);
+        Self::from_entries(entries, byte, arch).ok_or(OpcodeError::InvalidOpcode(byte))
     }
 
     pub fn convert_with_ext_arch(
@@ -726,88 +466,17 @@ impl Opcode {
         ext: RegFieldExt,
         arch: Arch,
     ) -> Result<(), OpcodeError> {
-        // We know the following extensions only have 2 operands
-        match self.ident {
-            OpcodeType::NeedsModRMExtension(byte) => match byte {
-                0x80 => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                    self.operands[1] =
-                        Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                    self.encoding = Some(OperandEncoding::MI);
-                }
-                0x81 => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                    self.operands[1] =
-                        Some(Operand::from_map(AddressingMethod::I, OperandType::Z, arch));
-                    self.encoding = Some(OperandEncoding::MI);
-                }
-                0x82 => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::B, arch));
-                    self.operands[1] =
-                        Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                    self.encoding = Some(OperandEncoding::MI);
-                }
-                0x83 => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                    self.operands[1] =
-                        Some(Operand::from_map(AddressingMethod::I, OperandType::B, arch));
-                    self.encoding = Some(OperandEncoding::MI);
-                }
-                0x8F => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                    self.encoding = Some(OperandEncoding::M);
-                }
-                0xFF => {
-                    self.operands[0] =
-                        Some(Operand::from_map(AddressingMethod::E, OperandType::V, arch));
-                    self.encoding = Some(OperandEncoding::MI);
-                }
-                _ => {}
-            },
-            _ => {}
-        };
-
         if let OpcodeType::NeedsModRMExtension(byte) = self.ident {
-            // Depending on the opcode, we have specific identificators for instructions
-            match byte {
-                0x80 | 0x81 | 0x82 | 0x83 => {
-                    self.ident = match ext.0 {
-                        0 => OpcodeType::Add,
-                        1 => OpcodeType::Or,
-                        2 => OpcodeType::Adc,
-                        3 => OpcodeType::Sbb,
-                        4 => OpcodeType::And,
-                        5 => OpcodeType::Sub,
-                        6 => OpcodeType::Xor,
-                        7 => OpcodeType::Cmp,
-                        _ => unreachable!(),
-                    };
-                }
-                0x8F => {
-                    self.ident = match ext.0 {
-                        0 => OpcodeType::Pop,
-                        _ => unreachable!(),
-                    };
-                }
-                0xFF => {
-                    self.ident = match ext.0 {
-                        0 => OpcodeType::Inc,
-                        1 => OpcodeType::Dec,
-                        2 => OpcodeType::CallNear,
-                        3 => OpcodeType::CallFar,
-                        4 => OpcodeType::JmpNear,
-                        5 => OpcodeType::JmpFar,
-                        6 => OpcodeType::Push,
-                        _ => unreachable!(),
-                    };
-                }
-                _ => todo!(),
-            }
+            // The remaining extensions of a group are either reserved or belong to encodings(like
+            // XOP for 0x8F) that we do not support
+            let entry = self
+                .group
+                .iter()
+                .find(|entry| {
+                    entry.modrm == ModRMSelector::Reg(ext.0) && entry.modes.valid_in(arch)
+                })
+                .ok_or(OpcodeError::InvalidExtension(byte, ext.0))?;
+            *self = Self::from_entry(entry, byte, arch);
         }
 
         Ok(())
@@ -824,125 +493,151 @@ impl Opcode {
         // Read the first byte from the `reader`
         let first_byte = reader.read::<u8>()?;
 
-        // Check where the first byte we read is an escaped code or not.
-        match first_byte {
-            // If we found an escape code, than we know that the Opcode is 2 or 3 bytes long
+        if let Some(opcode) = Self::from_prefix_byte(first_byte, arch) {
+            return Ok(opcode);
+        }
+
+        // If we found an escape code, than we know that the Opcode is 2 or 3 bytes long and the
+        // next bytes select the opcode map
+        let (map, byte, invalid) = match first_byte {
             opcode_prefix::ESCAPE_CODE => {
-                match prefixs.len() {
-                    0 => {
-                        let second_byte = reader.read::<u8>()?;
-                        match second_byte {
-                            // Push FS Selector
-                            0xA0 => {
-                                let mut operands = [None, None, None, None];
-                                operands[0] = Some(Operand::Segment(SegmentRegister::FS));
-                                let encoding = Some(OperandEncoding::ZO);
-                                Ok(Opcode {
-                                    ident: OpcodeType::Push,
-                                    operands,
-                                    encoding,
-                                })
-                            }
-                            // Pop FS Selector
-                            0xA1 => {
-                                let mut operands = [None, None, None, None];
-                                operands[0] = Some(Operand::Segment(SegmentRegister::FS));
-                                let encoding = Some(OperandEncoding::ZO);
-                                Ok(Opcode {
-                                    ident: OpcodeType::Pop,
-                                    operands,
-                                    encoding,
-                                })
-                            }
-                            // Push GS Selector
-                            0xA8 => {
-                                let mut operands = [None, None, None, None];
-                                operands[0] = Some(Operand::Segment(SegmentRegister::GS));
-                                let encoding = Some(OperandEncoding::ZO);
-                                Ok(Opcode {
-                                    ident: OpcodeType::Push,
-                                    operands,
-                                    encoding,
-                                })
-                            }
-                            // Pop GS Selector
-                            0xA9 => {
-                                let mut operands = [None, None, None, None];
-                                operands[0] = Some(Operand::Segment(SegmentRegister::GS));
-                                let encoding = Some(OperandEncoding::ZO);
-                                Ok(Opcode {
-                                    ident: OpcodeType::Pop,
-                                    operands,
-                                    encoding,
-                                })
-                            }
-                            _ => Err(OpcodeError::InvalidOpcode(second_byte)),
-                        }
-                    }
-                    _ => {
-                        let prefix = prefixs[0];
-                        match prefix {
-                            Prefix::Group1(gr1) => {
-                                match gr1 {
-                                    Group1::RepNE => Ok(Opcode {
-                                        ident: OpcodeType::Unknown,
-                                        operands: [None, None, None, None],
-                                        encoding: None,
-                                    }),
-                                    Group1::Rep => {
-                                        let second_byte = reader.read::<u8>()?;
-                                        match second_byte {
-                                            // This is the byte that indicates an ENDBR
-                                            0x1E => {
-                                                // We have to read a 3rd byte
-                                                let third_byte = reader.read::<u8>()?;
-                                                match third_byte {
-                                                    0xFB => Ok(Opcode {
-                                                        ident: OpcodeType::EndBr32,
-                                                        operands: [None, None, None, None],
-                                                        encoding: Some(OperandEncoding::ZO),
-                                                    }),
-                                                    0xFA => Ok(Opcode {
-                                                        ident: OpcodeType::EndBr64,
-                                                        operands: [None, None, None, None],
-                                                        encoding: Some(OperandEncoding::ZO),
-                                                    }),
-                                                    _ => Err(OpcodeError::Invalid3ByteOpcode(
-                                                        first_byte,
-                                                        second_byte,
-                                                        third_byte,
-                                                    )),
-                                                }
-                                            }
-                                            _ => Ok(Opcode {
-                                                ident: OpcodeType::Unknown,
-                                                operands: [None, None, None, None],
-                                                encoding: None,
-                                            }),
-                                        }
-                                    }
-                                    _ => Err(OpcodeError::InvalidPrefix(prefix)),
-                                }
-                            }
-                            Prefix::OpSize => Ok(Opcode {
-                                ident: OpcodeType::Unknown,
-                                operands: [None, None, None, None],
-                                encoding: None,
-                            }),
-                            // If we have an escape code, any other prefix is invalid for a 2-byte,
-                            // 3-byte opcode
-                            _ => Err(OpcodeError::InvalidPrefix(prefix)),
-                        }
+                let second_byte = reader.read::<u8>()?;
+                match second_byte {
+                    opcode_prefix::MAP_0F38 | opcode_prefix::MAP_0F3A => {
+                        let third_byte = reader.read::<u8>()?;
+                        let map = match second_byte {
+                            opcode_prefix::MAP_0F38 => OpcodeMap::Map0F38,
+                            _ => OpcodeMap::Map0F3A,
+                        };
+                        let err =
+                            OpcodeError::Invalid3ByteOpcode(first_byte, second_byte, third_byte);
+                        (map, third_byte, err)
                     }
+                    _ => (
+                        OpcodeMap::Map0F,
+                        second_byte,
+                        OpcodeError::InvalidOpcode(second_byte),
+                    ),
                 }
             }
-            // If the byte is not an escape code, that means it is just a 1-byte
-            // opcode, that we have to parse.
-            _ => Self::from_byte_with_arch(first_byte, arch),
+            _ => (
+                OpcodeMap::OneByte,
+                first_byte,
+                OpcodeError::InvalidOpcode(first_byte),
+            ),
+        };
+
+        let entries = table::lookup(map, byte, MandatoryPrefix::from_prefixes(prefixs));
+
+        // Some opcodes use the whole ModRM byte to select the instruction, in which case the
+        // ModRM byte is part of the opcode and we consume it
+        if entries
+            .iter()
+            .any(|entry| matches!(entry.modrm, ModRMSelector::Byte(_)))
+        {
+            let modrm = reader.peek::<u8>()?;
+            let entry = entries.iter().find(|entry| {
+                entry.modrm == ModRMSelector::Byte(modrm) && entry.modes.valid_in(arch)
+            });
+            if let Some(entry) = entry {
+                reader.read::<u8>()?;
+                return Ok(Self::from_entry(entry, byte, arch));
+            }
+            if map == OpcodeMap::Map0F {
+                return Self::from_entries(entries, byte, arch)
+                    .ok_or(OpcodeError::Invalid3ByteOpcode(first_byte, byte, modrm));
+            }
+        }
+
+        Self::from_entries(entries, byte, arch).ok_or(invalid)
+    }
+
+    // Returns the opcode for a legacy prefix or, in 64-bit mode, a REX prefix
+    fn from_prefix_byte(byte: u8, arch: Arch) -> Option<Self> {
+        // REX prefixes only exist in 64-bit mode, otherwise the same bytes encode `INC` and `DEC`
+        let ident = match (Prefix::from_byte(byte), arch) {
+            (Some(prefix), _) => OpcodeType::Prefix(prefix),
+            (None, Arch::Arch64) => OpcodeType::Rex(Rex::from_byte(byte)?),
+            (None, _) => return None,
+        };
+
+        Some(Self::without_operands(ident, &[]))
+    }
+
+    // Returns the opcode for the first of `entries` which is valid in `arch`, not counting the
+    // entries selected by a whole ModRM byte. If it belongs to a group, the instruction is only
+    // known once we read the ModRM.reg field
+    fn from_entries(entries: &'static [OpcodeEntry], byte: u8, arch: Arch) -> Option<Self> {
+        let entry = entries.iter().find(|entry| {
+            !matches!(entry.modrm, ModRMSelector::Byte(_)) && entry.modes.valid_in(arch)
+        })?;
+
+        match entry.modrm {
+            ModRMSelector::Reg(_) => Some(Self::without_operands(
+                OpcodeType::NeedsModRMExtension(byte),
+                entries,
+            )),
+            _ => Some(Self::from_entry(entry, byte, arch)),
+        }
+    }
+
+    fn from_entry(entry: &OpcodeEntry, byte: u8, arch: Arch) -> Self {
+        Self {
+            ident: entry.ident,
+            mnemonic: entry.mnemonic,
+            operands: entry
+                .operands
+                .map(|operand| operand.map(|(spec, access)| (spec.to_operand(byte, arch), access))),
+            specs: entry.operands.map(|operand| operand.map(|(spec, _)| spec)),
+            implicit: entry
+                .implicit
+                .map(|operand| operand.map(|(spec, access)| (spec.to_operand(byte, arch), access))),
+            encoding: Some(entry.encoding),
+            extension: entry.extension,
+            prefix: entry.prefix,
+            group: &[],
+        }
+    }
+
+    /// Converts `nop` to the `xchg` it is an alias of, when REX.B selects another register than
+    /// the accumulator, like `xchg r8, rax` for `49 90`
+    pub fn convert_with_rex(&mut self, maybe_rex: Option<Rex>, arch: Arch) {
+        let is_nop = self.ident == OpcodeType::Nop && self.encoding == Some(OperandEncoding::ZO);
+        let rex_b = maybe_rex.is_some_and(|rex| rex.b() == 1);
+        if !(is_nop && rex_b) {
+            return;
+        }
+        let entries = table::lookup(
+            OpcodeMap::OneByte,
+            opcode_prefix::NOP,
+            MandatoryPrefix::None,
+        );
+        if let Some(entry) = entries.iter().find(|entry| entry.ident == OpcodeType::Xchg) {
+            *self = Self::from_entry(entry, opcode_prefix::NOP, arch);
+        }
+    }
+
+    // Prefixes and opcode groups have no mnemonic, since they are not instructions by themselves
+    fn without_operands(ident: OpcodeType, group: &'static [OpcodeEntry]) -> Self {
+        Self {
+            ident,
+            mnemonic: "",
+            operands: [None, None, None, None],
+            specs: [None, None, None, None],
+            implicit: [None, None, None, None],
+            encoding: None,
+            extension: None,
+            prefix: MandatoryPrefix::None,
+            group,
         }
     }
 }
 
 mod opcode_prefix {
     pub const ESCAPE_CODE: u8 = 0x0F;
+    // Second escape bytes, which select the 3-byte opcode maps
+    pub const MAP_0F38: u8 = 0x38;
+    pub const MAP_0F3A: u8 = 0x3A;
+    // `nop`, which shares its opcode with `xchg eax, eax`
+    pub const NOP: u8 = 0x90;
 }
@@ -1,4 +1,5 @@
 //! Module that handles x86_64 Instruction Prefixes parsing
+use crate::reg::SegmentRegister;
 
 /// Represents instruction prefixes of 1 byte each. They are divided into four groups, each
 /// with a set of allowable prefix codes.
@@ -91,6 +92,20 @@ pub enum Group2 {
     GsSegOverride,
 }
 
+impl Group2 {
+    /// Returns the segment register the prefix selects
+    pub fn segment(&self) -> SegmentRegister {
+        match self {
+            Self::CsSegOverride => SegmentRegister::CS,
+            Self::SsSegOverride => SegmentRegister::SS,
+            Self::DsSegOverride => SegmentRegister::DS,
+            Self::EsSegOverride => SegmentRegister::ES,
+            Self::FsSegOverride => SegmentRegister::FS,
+            Self::GsSegOverride => SegmentRegister::GS,
+        }
+    }
+}
+
 impl TryFrom<u8> for Group2 {
     type Error = PrefixError;
 
@@ -11,9 +11,10 @@ pub struct Reader {
 }
 
 /// General error raised when one of the `Reader` methods fails
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ReaderError {
-    NotEnoughBytes,
+    // The read needs this many more bytes than the buffer holds
+    NotEnoughBytes(usize),
     TryFromSliceError(TryFromSliceError),
     AlreadyRecording,
     RecordingNotStarted,
@@ -28,7 +29,11 @@ impl From<TryFromSliceError> for ReaderError {
 impl Reader {
     /// Create a new `Reader` from a vector of bytes
     pub fn from_vec(bytes: Vec<u8>) -> Self {
-        Self { pos: 0, bytes, record_pos: None }
+        Self {
+            pos: 0,
+            bytes,
+            record_pos: None,
+        }
     }
 
     pub fn bytes_unread(&self) -> usize {
@@ -39,6 +44,42 @@ impl Reader {
         self.pos
     }
 
+    /// Returns the bytes read from position `pos` until the current cursor. If `pos` is past the
+    /// cursor, nothing was read since then and the returned slice is empty
+    pub fn bytes_since(&self, pos: usize) -> &[u8] {
+        self.bytes.get(pos..self.pos).unwrap_or(&[])
+    }
+
+    /// Appends `bytes` at the end of the buffer, such that they can be read after the bytes that
+    /// are already there
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Drops the bytes that were already read, such that the buffer does not keep growing when
+    /// we `extend` it. If we are recording, only the bytes before the recording are dropped.
+    /// Returns the number of dropped bytes, by which all the positions in the buffer moved back.
+    pub fn drop_read(&mut self) -> usize {
+        let dropped = self.record_pos.map_or(self.pos, |pos| pos.min(self.pos));
+        self.bytes.drain(..dropped);
+        self.pos -= dropped;
+        self.record_pos = self.record_pos.map(|pos| pos - dropped);
+        dropped
+    }
+
+    /// Moves the cursor to `pos`, such that the next read starts from there
+    ///
+    /// # Errors
+    ///
+    /// Fails if `pos` is past the end of the buffer
+    pub fn seek(&mut self, pos: usize) -> Result<(), ReaderError> {
+        if pos > self.bytes.len() {
+            return Err(ReaderError::NotEnoughBytes(pos - self.bytes.len()));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
     /// Reads `size` bytes from the buffer that back this `Reader` and moves the buffer pointer
     /// forward by `size` bytes
     pub fn read_bytes(&mut self, size: usize) -> Result<&[u8], ReaderError> {
@@ -46,7 +87,7 @@ impl Reader {
         let bytes_read = self
             .bytes
             .get(self.pos..self.pos + size)
-            .ok_or(ReaderError::NotEnoughBytes)?;
+            .ok_or_else(|| ReaderError::NotEnoughBytes(size - self.bytes_unread()))?;
 
         // If we successfully read the bytes, we move the pointer by `size`
         self.pos += size;
@@ -58,7 +99,7 @@ impl Reader {
     /// Sets a checkpoint to the current cursor where the reader will know to start recording all
     /// the bytes that we read
     pub fn start_recording(&mut self) -> Result<(), ReaderError> {
-        if let None = self.record_pos {
+        if self.record_pos.is_none() {
             self.record_pos = Some(self.pos);
             Ok(())
         } else {
@@ -76,12 +117,12 @@ impl Reader {
             let bytes_read = self
                 .bytes
                 .get(pos..self.pos)
-                .ok_or(ReaderError::NotEnoughBytes)?;
+                .ok_or_else(|| ReaderError::NotEnoughBytes(pos - self.pos))?;
 
             self.record_pos = None;
 
             Ok(bytes_read)
-        } else { 
+        } else {
             Err(ReaderError::RecordingNotStarted)
         }
     }
@@ -95,7 +136,7 @@ impl Reader {
         let bytes_read = self
             .bytes
             .get(self.pos..self.pos + size)
-            .ok_or(ReaderError::NotEnoughBytes)?;
+            .ok_or_else(|| ReaderError::NotEnoughBytes(size - self.bytes_unread()))?;
 
         // Return the read bytes
         Ok(bytes_read)
@@ -1,6 +1,9 @@
-use crate::{inst::SizedOperand, modrm::Arch, opcode::OpSize};
+use crate::{arch::Arch, inst::SizedOperand, opcode::OpSize};
 use core::fmt;
 
+/// A register of the processor. Every register belongs to a `RegClass`, and registers which
+/// share their storage, like `al`, `ax`, `eax` and `rax`, or `xmm0` and `zmm0`, have the same
+/// `parent`.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Reg {
     AL,
@@ -87,86 +90,204 @@ pub enum Reg {
     DIL,
     SPL,
     BPL,
+    XMM8,
+    XMM9,
+    XMM10,
+    XMM11,
+    XMM12,
+    XMM13,
+    XMM14,
+    XMM15,
+    XMM16,
+    XMM17,
+    XMM18,
+    XMM19,
+    XMM20,
+    XMM21,
+    XMM22,
+    XMM23,
+    XMM24,
+    XMM25,
+    XMM26,
+    XMM27,
+    XMM28,
+    XMM29,
+    XMM30,
+    XMM31,
+    YMM0,
+    YMM1,
+    YMM2,
+    YMM3,
+    YMM4,
+    YMM5,
+    YMM6,
+    YMM7,
+    YMM8,
+    YMM9,
+    YMM10,
+    YMM11,
+    YMM12,
+    YMM13,
+    YMM14,
+    YMM15,
+    YMM16,
+    YMM17,
+    YMM18,
+    YMM19,
+    YMM20,
+    YMM21,
+    YMM22,
+    YMM23,
+    YMM24,
+    YMM25,
+    YMM26,
+    YMM27,
+    YMM28,
+    YMM29,
+    YMM30,
+    YMM31,
+    ZMM0,
+    ZMM1,
+    ZMM2,
+    ZMM3,
+    ZMM4,
+    ZMM5,
+    ZMM6,
+    ZMM7,
+    ZMM8,
+    ZMM9,
+    ZMM10,
+    ZMM11,
+    ZMM12,
+    ZMM13,
+    ZMM14,
+    ZMM15,
+    ZMM16,
+    ZMM17,
+    ZMM18,
+    ZMM19,
+    ZMM20,
+    ZMM21,
+    ZMM22,
+    ZMM23,
+    ZMM24,
+    ZMM25,
+    ZMM26,
+    ZMM27,
+    ZMM28,
+    ZMM29,
+    ZMM30,
+    ZMM31,
+    K0,
+    K1,
+    K2,
+    K3,
+    K4,
+    K5,
+    K6,
+    K7,
+    ST0,
+    ST1,
+    ST2,
+    ST3,
+    ST4,
+    ST5,
+    ST6,
+    ST7,
+    CR0,
+    CR1,
+    CR2,
+    CR3,
+    CR4,
+    CR5,
+    CR6,
+    CR7,
+    CR8,
+    CR9,
+    CR10,
+    CR11,
+    CR12,
+    CR13,
+    CR14,
+    CR15,
+    DR0,
+    DR1,
+    DR2,
+    DR3,
+    DR4,
+    DR5,
+    DR6,
+    DR7,
+    DR8,
+    DR9,
+    DR10,
+    DR11,
+    DR12,
+    DR13,
+    DR14,
+    DR15,
+    ES,
+    CS,
+    SS,
+    DS,
+    FS,
+    GS,
+    IP,
+    EIP,
+    RIP,
 }
 
 impl fmt::Display for Reg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", format!("{self:?}").to_lowercase())
+        match self.class() {
+            // The x87 registers are relative to the top of the stack
+            RegClass::X87 => write!(f, "st({})", self.index()),
+            _ => write!(f, "{}", format!("{self:?}").to_lowercase()),
+        }
     }
 }
 
 impl SizedOperand for Reg {
     fn size(&self) -> OpSize {
-        match self {
-            Reg::AL
-            | Reg::CL
-            | Reg::DL
-            | Reg::BL
-            | Reg::SPL
-            | Reg::BPL
-            | Reg::SIL
-            | Reg::DIL
-            | Reg::R8b
-            | Reg::R9b
-            | Reg::R10b
-            | Reg::R11b
-            | Reg::R12b
-            | Reg::R13b
-            | Reg::R14b
-            | Reg::R15b => OpSize::U8,
-            Reg::AX
-            | Reg::CX
-            | Reg::DX
-            | Reg::BX
-            | Reg::SP
-            | Reg::BP
-            | Reg::SI
-            | Reg::DI
-            | Reg::R8w
-            | Reg::R9w
-            | Reg::R10w
-            | Reg::R11w
-            | Reg::R12w
-            | Reg::R13w
-            | Reg::R14w
-            | Reg::R15w => OpSize::U16,
-            Reg::EAX
-            | Reg::ECX
-            | Reg::EDX
-            | Reg::EBX
-            | Reg::ESP
-            | Reg::EBP
-            | Reg::ESI
-            | Reg::EDI
-            | Reg::R8d
-            | Reg::R9d
-            | Reg::R10d
-            | Reg::R11d
-            | Reg::R12d
-            | Reg::R13d
-            | Reg::R14d
-            | Reg::R15d => OpSize::U32,
-            Reg::RAX
-            | Reg::RCX
-            | Reg::RDX
-            | Reg::RBX
-            | Reg::RSP
-            | Reg::RBP
-            | Reg::RSI
-            | Reg::RDI
-            | Reg::R8
-            | Reg::R9
-            | Reg::R10
-            | Reg::R11
-            | Reg::R12
-            | Reg::R13
-            | Reg::R14
-            | Reg::R15 => OpSize::U64,
+        match self.width() {
+            8 => OpSize::U8,
+            16 => OpSize::U16,
+            32 => OpSize::U32,
+            64 => OpSize::U64,
+            // Registers larger than any operand size
             _ => OpSize::CpuMode,
         }
     }
 }
 
+/// The class of a register, which groups the registers of the same register file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegClass {
+    // General-purpose registers, of any width
+    Gpr,
+    // Segment registers
+    Segment,
+    // Control registers, CR0 to CR15
+    Control,
+    // Debug registers, DR0 to DR15
+    Debug,
+    // The instruction pointer, used by rip-relative addressing
+    InstructionPointer,
+    // The x87 FPU data registers
+    X87,
+    // The MMX registers, which are aliased to the mantissa of the x87 data registers. Since the
+    // x87 registers are relative to the top of the stack, we do not relate them to each other.
+    Mmx,
+    // The 128-bit SSE registers, which are the lower half of the YMM registers
+    Xmm,
+    // The 256-bit AVX registers, which are the lower half of the ZMM registers
+    Ymm,
+    // The 512-bit AVX-512 registers
+    Zmm,
+    // The AVX-512 opmask registers
+    Mask,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SegmentRegister {
     CS,
@@ -177,6 +298,19 @@ pub enum SegmentRegister {
     GS,
 }
 
+impl From<SegmentRegister> for Reg {
+    fn from(value: SegmentRegister) -> Self {
+        match value {
+            SegmentRegister::CS => Reg::CS,
+            SegmentRegister::DS => Reg::DS,
+            SegmentRegister::SS => Reg::SS,
+            SegmentRegister::ES => Reg::ES,
+            SegmentRegister::FS => Reg::FS,
+            SegmentRegister::GS => Reg::GS,
+        }
+    }
+}
+
 impl fmt::Display for SegmentRegister {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", format!("{self:?}").to_lowercase())
@@ -273,28 +407,27 @@ impl RegFamily {
 }
 
 pub trait Gpr {
-    const Reg8BitLo: Reg;
-    const Reg8BitHi: Option<Reg>;
-    const Reg16Bit: Reg;
-    const Reg32Bit: Reg;
-    const Reg64Bit: Reg;
+    const REG_8BIT_LO: Reg;
+    const REG_8BIT_HI: Option<Reg>;
+    const REG_16BIT: Reg;
+    const REG_32BIT: Reg;
+    const REG_64BIT: Reg;
 
     fn from_opsize(op_size: &OpSize) -> Reg {
         match op_size {
-            OpSize::U8 => Self::Reg8BitLo,
-            OpSize::U16 => Self::Reg16Bit,
-            OpSize::U32 => Self::Reg32Bit,
-            OpSize::U64 => Self::Reg64Bit,
-            _ => Self::Reg32Bit,
+            OpSize::U8 => Self::REG_8BIT_LO,
+            OpSize::U16 => Self::REG_16BIT,
+            OpSize::U32 => Self::REG_32BIT,
+            OpSize::U64 => Self::REG_64BIT,
+            _ => Self::REG_32BIT,
         }
     }
 
     fn from_arch(arch: &Arch) -> Reg {
         match arch {
-            Arch::Arch16 => Self::Reg16Bit,
-            Arch::Arch32 => Self::Reg32Bit,
-            Arch::Arch64 => Self::Reg64Bit,
-            _ => Self::Reg32Bit,
+            Arch::Arch16 => Self::REG_16BIT,
+            Arch::Arch32 => Self::REG_32BIT,
+            Arch::Arch64 => Self::REG_64BIT,
         }
     }
 }
@@ -303,202 +436,942 @@ pub trait Gpr {
 pub struct Accumulator;
 
 impl Gpr for Accumulator {
-    const Reg8BitLo: Reg = Reg::AL;
-    const Reg8BitHi: Option<Reg> = Some(Reg::AH);
-    const Reg16Bit: Reg = Reg::AX;
-    const Reg32Bit: Reg = Reg::EAX;
-    const Reg64Bit: Reg = Reg::RAX;
+    const REG_8BIT_LO: Reg = Reg::AL;
+    const REG_8BIT_HI: Option<Reg> = Some(Reg::AH);
+    const REG_16BIT: Reg = Reg::AX;
+    const REG_32BIT: Reg = Reg::EAX;
+    const REG_64BIT: Reg = Reg::RAX;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Counter;
 
 impl Gpr for Counter {
-    const Reg8BitLo: Reg = Reg::CL;
-    const Reg8BitHi: Option<Reg> = Some(Reg::CH);
-    const Reg16Bit: Reg = Reg::CX;
-    const Reg32Bit: Reg = Reg::ECX;
-    const Reg64Bit: Reg = Reg::RCX;
+    const REG_8BIT_LO: Reg = Reg::CL;
+    const REG_8BIT_HI: Option<Reg> = Some(Reg::CH);
+    const REG_16BIT: Reg = Reg::CX;
+    const REG_32BIT: Reg = Reg::ECX;
+    const REG_64BIT: Reg = Reg::RCX;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Data;
 
 impl Gpr for Data {
-    const Reg8BitLo: Reg = Reg::DL;
-    const Reg8BitHi: Option<Reg> = Some(Reg::DH);
-    const Reg16Bit: Reg = Reg::DX;
-    const Reg32Bit: Reg = Reg::EDX;
-    const Reg64Bit: Reg = Reg::RDX;
+    const REG_8BIT_LO: Reg = Reg::DL;
+    const REG_8BIT_HI: Option<Reg> = Some(Reg::DH);
+    const REG_16BIT: Reg = Reg::DX;
+    const REG_32BIT: Reg = Reg::EDX;
+    const REG_64BIT: Reg = Reg::RDX;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Base;
 
 impl Gpr for Base {
-    const Reg8BitLo: Reg = Reg::BL;
-    const Reg8BitHi: Option<Reg> = Some(Reg::BH);
-    const Reg16Bit: Reg = Reg::BX;
-    const Reg32Bit: Reg = Reg::EBX;
-    const Reg64Bit: Reg = Reg::RBX;
+    const REG_8BIT_LO: Reg = Reg::BL;
+    const REG_8BIT_HI: Option<Reg> = Some(Reg::BH);
+    const REG_16BIT: Reg = Reg::BX;
+    const REG_32BIT: Reg = Reg::EBX;
+    const REG_64BIT: Reg = Reg::RBX;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StackPointer;
 
 impl Gpr for StackPointer {
-    const Reg8BitLo: Reg = Reg::SPL;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::SP;
-    const Reg32Bit: Reg = Reg::ESP;
-    const Reg64Bit: Reg = Reg::RSP;
+    const REG_8BIT_LO: Reg = Reg::SPL;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::SP;
+    const REG_32BIT: Reg = Reg::ESP;
+    const REG_64BIT: Reg = Reg::RSP;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BasePointer;
 
 impl Gpr for BasePointer {
-    const Reg8BitLo: Reg = Reg::BPL;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::BP;
-    const Reg32Bit: Reg = Reg::EBP;
-    const Reg64Bit: Reg = Reg::RBP;
+    const REG_8BIT_LO: Reg = Reg::BPL;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::BP;
+    const REG_32BIT: Reg = Reg::EBP;
+    const REG_64BIT: Reg = Reg::RBP;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Source;
 
 impl Gpr for Source {
-    const Reg8BitLo: Reg = Reg::SIL;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::SI;
-    const Reg32Bit: Reg = Reg::ESI;
-    const Reg64Bit: Reg = Reg::RSI;
+    const REG_8BIT_LO: Reg = Reg::SIL;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::SI;
+    const REG_32BIT: Reg = Reg::ESI;
+    const REG_64BIT: Reg = Reg::RSI;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Destination;
 
 impl Gpr for Destination {
-    const Reg8BitLo: Reg = Reg::DIL;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::DI;
-    const Reg32Bit: Reg = Reg::EDI;
-    const Reg64Bit: Reg = Reg::RDI;
+    const REG_8BIT_LO: Reg = Reg::DIL;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::DI;
+    const REG_32BIT: Reg = Reg::EDI;
+    const REG_64BIT: Reg = Reg::RDI;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R8Reg;
 
 impl Gpr for R8Reg {
-    const Reg8BitLo: Reg = Reg::R8b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R8w;
-    const Reg32Bit: Reg = Reg::R8d;
-    const Reg64Bit: Reg = Reg::R8;
+    const REG_8BIT_LO: Reg = Reg::R8b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R8w;
+    const REG_32BIT: Reg = Reg::R8d;
+    const REG_64BIT: Reg = Reg::R8;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R9Reg;
 
 impl Gpr for R9Reg {
-    const Reg8BitLo: Reg = Reg::R9b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R9w;
-    const Reg32Bit: Reg = Reg::R9d;
-    const Reg64Bit: Reg = Reg::R9;
+    const REG_8BIT_LO: Reg = Reg::R9b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R9w;
+    const REG_32BIT: Reg = Reg::R9d;
+    const REG_64BIT: Reg = Reg::R9;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R10Reg;
 
 impl Gpr for R10Reg {
-    const Reg8BitLo: Reg = Reg::R10b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R10w;
-    const Reg32Bit: Reg = Reg::R10d;
-    const Reg64Bit: Reg = Reg::R10;
+    const REG_8BIT_LO: Reg = Reg::R10b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R10w;
+    const REG_32BIT: Reg = Reg::R10d;
+    const REG_64BIT: Reg = Reg::R10;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R11Reg;
 
 impl Gpr for R11Reg {
-    const Reg8BitLo: Reg = Reg::R11b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R11w;
-    const Reg32Bit: Reg = Reg::R11d;
-    const Reg64Bit: Reg = Reg::R11;
+    const REG_8BIT_LO: Reg = Reg::R11b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R11w;
+    const REG_32BIT: Reg = Reg::R11d;
+    const REG_64BIT: Reg = Reg::R11;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R12Reg;
 
 impl Gpr for R12Reg {
-    const Reg8BitLo: Reg = Reg::R12b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R12w;
-    const Reg32Bit: Reg = Reg::R12d;
-    const Reg64Bit: Reg = Reg::R12;
+    const REG_8BIT_LO: Reg = Reg::R12b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R12w;
+    const REG_32BIT: Reg = Reg::R12d;
+    const REG_64BIT: Reg = Reg::R12;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R13Reg;
 
 impl Gpr for R13Reg {
-    const Reg8BitLo: Reg = Reg::R13b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R13w;
-    const Reg32Bit: Reg = Reg::R13d;
-    const Reg64Bit: Reg = Reg::R13;
+    const REG_8BIT_LO: Reg = Reg::R13b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R13w;
+    const REG_32BIT: Reg = Reg::R13d;
+    const REG_64BIT: Reg = Reg::R13;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R14Reg;
 
 impl Gpr for R14Reg {
-    const Reg8BitLo: Reg = Reg::R14b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R14w;
-    const Reg32Bit: Reg = Reg::R14d;
-    const Reg64Bit: Reg = Reg::R14;
+    const REG_8BIT_LO: Reg = Reg::R14b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R14w;
+    const REG_32BIT: Reg = Reg::R14d;
+    const REG_64BIT: Reg = Reg::R14;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct R15Reg;
 
 impl Gpr for R15Reg {
-    const Reg8BitLo: Reg = Reg::R15b;
-    const Reg8BitHi: Option<Reg> = None;
-    const Reg16Bit: Reg = Reg::R15w;
-    const Reg32Bit: Reg = Reg::R15d;
-    const Reg64Bit: Reg = Reg::R15;
+    const REG_8BIT_LO: Reg = Reg::R15b;
+    const REG_8BIT_HI: Option<Reg> = None;
+    const REG_16BIT: Reg = Reg::R15w;
+    const REG_32BIT: Reg = Reg::R15d;
+    const REG_64BIT: Reg = Reg::R15;
 }
 
 impl Reg {
-    pub fn convert_with_opsize(self, op_size: &OpSize) -> Reg {
+    /// Returns the class of the register
+    pub fn class(&self) -> RegClass {
         match self {
-            Reg::AL | Reg::AH | Reg::AX | Reg::EAX | Reg::RAX => Accumulator::from_opsize(op_size),
-            Reg::CL | Reg::CH | Reg::CX | Reg::ECX | Reg::RCX => Counter::from_opsize(op_size),
-            Reg::DL | Reg::DH | Reg::DX | Reg::EDX | Reg::RDX => Data::from_opsize(op_size),
-            Reg::BL | Reg::BH | Reg::BX | Reg::EBX | Reg::RBX => Base::from_opsize(op_size),
-            Reg::SPL | Reg::SP | Reg::ESP | Reg::RSP => StackPointer::from_opsize(op_size),
-            Reg::BPL | Reg::BP | Reg::EBP | Reg::RBP => BasePointer::from_opsize(op_size),
-            Reg::SIL | Reg::SI | Reg::ESI | Reg::RSI => Source::from_opsize(op_size),
-            Reg::DIL | Reg::DI | Reg::EDI | Reg::RDI => Destination::from_opsize(op_size),
-            Reg::R8b | Reg::R8w | Reg::R8d | Reg::R8 => R8Reg::from_opsize(op_size),
-            Reg::R9b | Reg::R9w | Reg::R9d | Reg::R9 => R9Reg::from_opsize(op_size),
-            Reg::R10b | Reg::R10w | Reg::R10d | Reg::R10 => R10Reg::from_opsize(op_size),
-            Reg::R11b | Reg::R8w | Reg::R8d | Reg::R8 => R8Reg::from_opsize(op_size),
-            Reg::R12b | Reg::R12w | Reg::R12d | Reg::R12 => R12Reg::from_opsize(op_size),
-            Reg::R13b | Reg::R13w | Reg::R13d | Reg::R13 => R13Reg::from_opsize(op_size),
-            Reg::R14b | Reg::R14w | Reg::R14d | Reg::R14 => R14Reg::from_opsize(op_size),
-            Reg::R15b | Reg::R15w | Reg::R15d | Reg::R15 => R15Reg::from_opsize(op_size),
-            // There are only control and special registers left, which should be put in a
-            // different category
-            _ => unreachable!(),
+            Reg::AL
+            | Reg::AH
+            | Reg::AX
+            | Reg::EAX
+            | Reg::RAX
+            | Reg::CL
+            | Reg::CH
+            | Reg::CX
+            | Reg::ECX
+            | Reg::RCX
+            | Reg::DL
+            | Reg::DH
+            | Reg::DX
+            | Reg::EDX
+            | Reg::RDX
+            | Reg::BL
+            | Reg::BH
+            | Reg::BX
+            | Reg::EBX
+            | Reg::RBX
+            | Reg::SPL
+            | Reg::SP
+            | Reg::ESP
+            | Reg::RSP
+            | Reg::BPL
+            | Reg::BP
+            | Reg::EBP
+            | Reg::RBP
+            | Reg::SIL
+            | Reg::SI
+            | Reg::ESI
+            | Reg::RSI
+            | Reg::DIL
+            | Reg::DI
+            | Reg::EDI
+            | Reg::RDI
+            | Reg::R8b
+            | Reg::R8w
+            | Reg::R8d
+            | Reg::R8
+            | Reg::R9b
+            | Reg::R9w
+            | Reg::R9d
+            | Reg::R9
+            | Reg::R10b
+            | Reg::R10w
+            | Reg::R10d
+            | Reg::R10
+            | Reg::R11b
+            | Reg::R11w
+            | Reg::R11d
+            | Reg::R11
+            | Reg::R12b
+            | Reg::R12w
+            | Reg::R12d
+            | Reg::R12
+            | Reg::R13b
+            | Reg::R13w
+            | Reg::R13d
+            | Reg::R13
+            | Reg::R14b
+            | Reg::R14w
+            | Reg::R14d
+            | Reg::R14
+            | Reg::R15b
+            | Reg::R15w
+            | Reg::R15d
+            | Reg::R15 => RegClass::Gpr,
+            Reg::ES | Reg::CS | Reg::SS | Reg::DS | Reg::FS | Reg::GS => RegClass::Segment,
+            Reg::CR0
+            | Reg::CR1
+            | Reg::CR2
+            | Reg::CR3
+            | Reg::CR4
+            | Reg::CR5
+            | Reg::CR6
+            | Reg::CR7
+            | Reg::CR8
+            | Reg::CR9
+            | Reg::CR10
+            | Reg::CR11
+            | Reg::CR12
+            | Reg::CR13
+            | Reg::CR14
+            | Reg::CR15 => RegClass::Control,
+            Reg::DR0
+            | Reg::DR1
+            | Reg::DR2
+            | Reg::DR3
+            | Reg::DR4
+            | Reg::DR5
+            | Reg::DR6
+            | Reg::DR7
+            | Reg::DR8
+            | Reg::DR9
+            | Reg::DR10
+            | Reg::DR11
+            | Reg::DR12
+            | Reg::DR13
+            | Reg::DR14
+            | Reg::DR15 => RegClass::Debug,
+            Reg::IP | Reg::EIP | Reg::RIP => RegClass::InstructionPointer,
+            Reg::ST0
+            | Reg::ST1
+            | Reg::ST2
+            | Reg::ST3
+            | Reg::ST4
+            | Reg::ST5
+            | Reg::ST6
+            | Reg::ST7 => RegClass::X87,
+            Reg::MM0
+            | Reg::MM1
+            | Reg::MM2
+            | Reg::MM3
+            | Reg::MM4
+            | Reg::MM5
+            | Reg::MM6
+            | Reg::MM7 => RegClass::Mmx,
+            Reg::XMM0
+            | Reg::XMM1
+            | Reg::XMM2
+            | Reg::XMM3
+            | Reg::XMM4
+            | Reg::XMM5
+            | Reg::XMM6
+            | Reg::XMM7
+            | Reg::XMM8
+            | Reg::XMM9
+            | Reg::XMM10
+            | Reg::XMM11
+            | Reg::XMM12
+            | Reg::XMM13
+            | Reg::XMM14
+            | Reg::XMM15
+            | Reg::XMM16
+            | Reg::XMM17
+            | Reg::XMM18
+            | Reg::XMM19
+            | Reg::XMM20
+            | Reg::XMM21
+            | Reg::XMM22
+            | Reg::XMM23
+            | Reg::XMM24
+            | Reg::XMM25
+            | Reg::XMM26
+            | Reg::XMM27
+            | Reg::XMM28
+            | Reg::XMM29
+            | Reg::XMM30
+            | Reg::XMM31 => RegClass::Xmm,
+            Reg::YMM0
+            | Reg::YMM1
+            | Reg::YMM2
+            | Reg::YMM3
+            | Reg::YMM4
+            | Reg::YMM5
+            | Reg::YMM6
+            | Reg::YMM7
+            | Reg::YMM8
+            | Reg::YMM9
+            | Reg::YMM10
+            | Reg::YMM11
+            | Reg::YMM12
+            | Reg::YMM13
+            | Reg::YMM14
+            | Reg::YMM15
+            | Reg::YMM16
+            | Reg::YMM17
+            | Reg::YMM18
+            | Reg::YMM19
+            | Reg::YMM20
+            | Reg::YMM21
+            | Reg::YMM22
+            | Reg::YMM23
+            | Reg::YMM24
+            | Reg::YMM25
+            | Reg::YMM26
+            | Reg::YMM27
+            | Reg::YMM28
+            | Reg::YMM29
+            | Reg::YMM30
+            | Reg::YMM31 => RegClass::Ymm,
+            Reg::ZMM0
+            | Reg::ZMM1
+            | Reg::ZMM2
+            | Reg::ZMM3
+            | Reg::ZMM4
+            | Reg::ZMM5
+            | Reg::ZMM6
+            | Reg::ZMM7
+            | Reg::ZMM8
+            | Reg::ZMM9
+            | Reg::ZMM10
+            | Reg::ZMM11
+            | Reg::ZMM12
+            | Reg::ZMM13
+            | Reg::ZMM14
+            | Reg::ZMM15
+            | Reg::ZMM16
+            | Reg::ZMM17
+            | Reg::ZMM18
+            | Reg::ZMM19
+            | Reg::ZMM20
+            | Reg::ZMM21
+            | Reg::ZMM22
+            | Reg::ZMM23
+            | Reg::ZMM24
+            | Reg::ZMM25
+            | Reg::ZMM26
+            | Reg::ZMM27
+            | Reg::ZMM28
+            | Reg::ZMM29
+            | Reg::ZMM30
+            | Reg::ZMM31 => RegClass::Zmm,
+            Reg::K0 | Reg::K1 | Reg::K2 | Reg::K3 | Reg::K4 | Reg::K5 | Reg::K6 | Reg::K7 => {
+                RegClass::Mask
+            }
+        }
+    }
+
+    /// Returns the number of the register within its class, which is also the number that
+    /// encodes it. The high byte registers `ah`, `ch`, `dh` and `bh` have the number of their
+    /// parent.
+    pub fn index(&self) -> u8 {
+        match self {
+            Reg::AL
+            | Reg::AH
+            | Reg::AX
+            | Reg::EAX
+            | Reg::RAX
+            | Reg::ES
+            | Reg::CR0
+            | Reg::DR0
+            | Reg::ST0
+            | Reg::MM0
+            | Reg::XMM0
+            | Reg::YMM0
+            | Reg::ZMM0
+            | Reg::K0
+            | Reg::IP
+            | Reg::EIP
+            | Reg::RIP => 0,
+            Reg::CL
+            | Reg::CH
+            | Reg::CX
+            | Reg::ECX
+            | Reg::RCX
+            | Reg::CS
+            | Reg::CR1
+            | Reg::DR1
+            | Reg::ST1
+            | Reg::MM1
+            | Reg::XMM1
+            | Reg::YMM1
+            | Reg::ZMM1
+            | Reg::K1 => 1,
+            Reg::DL
+            | Reg::DH
+            | Reg::DX
+            | Reg::EDX
+            | Reg::RDX
+            | Reg::SS
+            | Reg::CR2
+            | Reg::DR2
+            | Reg::ST2
+            | Reg::MM2
+            | Reg::XMM2
+            | Reg::YMM2
+            | Reg::ZMM2
+            | Reg::K2 => 2,
+            Reg::BL
+            | Reg::BH
+            | Reg::BX
+            | Reg::EBX
+            | Reg::RBX
+            | Reg::DS
+            | Reg::CR3
+            | Reg::DR3
+            | Reg::ST3
+            | Reg::MM3
+            | Reg::XMM3
+            | Reg::YMM3
+            | Reg::ZMM3
+            | Reg::K3 => 3,
+            Reg::SPL
+            | Reg::SP
+            | Reg::ESP
+            | Reg::RSP
+            | Reg::FS
+            | Reg::CR4
+            | Reg::DR4
+            | Reg::ST4
+            | Reg::MM4
+            | Reg::XMM4
+            | Reg::YMM4
+            | Reg::ZMM4
+            | Reg::K4 => 4,
+            Reg::BPL
+            | Reg::BP
+            | Reg::EBP
+            | Reg::RBP
+            | Reg::GS
+            | Reg::CR5
+            | Reg::DR5
+            | Reg::ST5
+            | Reg::MM5
+            | Reg::XMM5
+            | Reg::YMM5
+            | Reg::ZMM5
+            | Reg::K5 => 5,
+            Reg::SIL
+            | Reg::SI
+            | Reg::ESI
+            | Reg::RSI
+            | Reg::CR6
+            | Reg::DR6
+            | Reg::ST6
+            | Reg::MM6
+            | Reg::XMM6
+            | Reg::YMM6
+            | Reg::ZMM6
+            | Reg::K6 => 6,
+            Reg::DIL
+            | Reg::DI
+            | Reg::EDI
+            | Reg::RDI
+            | Reg::CR7
+            | Reg::DR7
+            | Reg::ST7
+            | Reg::MM7
+            | Reg::XMM7
+            | Reg::YMM7
+            | Reg::ZMM7
+            | Reg::K7 => 7,
+            Reg::R8b
+            | Reg::R8w
+            | Reg::R8d
+            | Reg::R8
+            | Reg::CR8
+            | Reg::DR8
+            | Reg::XMM8
+            | Reg::YMM8
+            | Reg::ZMM8 => 8,
+            Reg::R9b
+            | Reg::R9w
+            | Reg::R9d
+            | Reg::R9
+            | Reg::CR9
+            | Reg::DR9
+            | Reg::XMM9
+            | Reg::YMM9
+            | Reg::ZMM9 => 9,
+            Reg::R10b
+            | Reg::R10w
+            | Reg::R10d
+            | Reg::R10
+            | Reg::CR10
+            | Reg::DR10
+            | Reg::XMM10
+            | Reg::YMM10
+            | Reg::ZMM10 => 10,
+            Reg::R11b
+            | Reg::R11w
+            | Reg::R11d
+            | Reg::R11
+            | Reg::CR11
+            | Reg::DR11
+            | Reg::XMM11
+            | Reg::YMM11
+            | Reg::ZMM11 => 11,
+            Reg::R12b
+            | Reg::R12w
+            | Reg::R12d
+            | Reg::R12
+            | Reg::CR12
+            | Reg::DR12
+            | Reg::XMM12
+            | Reg::YMM12
+            | Reg::ZMM12 => 12,
+            Reg::R13b
+            | Reg::R13w
+            | Reg::R13d
+            | Reg::R13
+            | Reg::CR13
+            | Reg::DR13
+            | Reg::XMM13
+            | Reg::YMM13
+            | Reg::ZMM13 => 13,
+            Reg::R14b
+            | Reg::R14w
+            | Reg::R14d
+            | Reg::R14
+            | Reg::CR14
+            | Reg::DR14
+            | Reg::XMM14
+            | Reg::YMM14
+            | Reg::ZMM14 => 14,
+            Reg::R15b
+            | Reg::R15w
+            | Reg::R15d
+            | Reg::R15
+            | Reg::CR15
+            | Reg::DR15
+            | Reg::XMM15
+            | Reg::YMM15
+            | Reg::ZMM15 => 15,
+            Reg::XMM16 | Reg::YMM16 | Reg::ZMM16 => 16,
+            Reg::XMM17 | Reg::YMM17 | Reg::ZMM17 => 17,
+            Reg::XMM18 | Reg::YMM18 | Reg::ZMM18 => 18,
+            Reg::XMM19 | Reg::YMM19 | Reg::ZMM19 => 19,
+            Reg::XMM20 | Reg::YMM20 | Reg::ZMM20 => 20,
+            Reg::XMM21 | Reg::YMM21 | Reg::ZMM21 => 21,
+            Reg::XMM22 | Reg::YMM22 | Reg::ZMM22 => 22,
+            Reg::XMM23 | Reg::YMM23 | Reg::ZMM23 => 23,
+            Reg::XMM24 | Reg::YMM24 | Reg::ZMM24 => 24,
+            Reg::XMM25 | Reg::YMM25 | Reg::ZMM25 => 25,
+            Reg::XMM26 | Reg::YMM26 | Reg::ZMM26 => 26,
+            Reg::XMM27 | Reg::YMM27 | Reg::ZMM27 => 27,
+            Reg::XMM28 | Reg::YMM28 | Reg::ZMM28 => 28,
+            Reg::XMM29 | Reg::YMM29 | Reg::ZMM29 => 29,
+            Reg::XMM30 | Reg::YMM30 | Reg::ZMM30 => 30,
+            Reg::XMM31 | Reg::YMM31 | Reg::ZMM31 => 31,
+        }
+    }
+
+    /// Returns the width of the register in bits
+    pub fn width(&self) -> u16 {
+        match self {
+            Reg::AL
+            | Reg::CL
+            | Reg::DL
+            | Reg::BL
+            | Reg::SPL
+            | Reg::BPL
+            | Reg::SIL
+            | Reg::DIL
+            | Reg::R8b
+            | Reg::R9b
+            | Reg::R10b
+            | Reg::R11b
+            | Reg::R12b
+            | Reg::R13b
+            | Reg::R14b
+            | Reg::R15b
+            | Reg::AH
+            | Reg::CH
+            | Reg::DH
+            | Reg::BH => 8,
+            Reg::AX
+            | Reg::CX
+            | Reg::DX
+            | Reg::BX
+            | Reg::SP
+            | Reg::BP
+            | Reg::SI
+            | Reg::DI
+            | Reg::R8w
+            | Reg::R9w
+            | Reg::R10w
+            | Reg::R11w
+            | Reg::R12w
+            | Reg::R13w
+            | Reg::R14w
+            | Reg::R15w
+            | Reg::ES
+            | Reg::CS
+            | Reg::SS
+            | Reg::DS
+            | Reg::FS
+            | Reg::GS
+            | Reg::IP => 16,
+            Reg::EAX
+            | Reg::ECX
+            | Reg::EDX
+            | Reg::EBX
+            | Reg::ESP
+            | Reg::EBP
+            | Reg::ESI
+            | Reg::EDI
+            | Reg::R8d
+            | Reg::R9d
+            | Reg::R10d
+            | Reg::R11d
+            | Reg::R12d
+            | Reg::R13d
+            | Reg::R14d
+            | Reg::R15d
+            | Reg::EIP => 32,
+            Reg::RAX
+            | Reg::RCX
+            | Reg::RDX
+            | Reg::RBX
+            | Reg::RSP
+            | Reg::RBP
+            | Reg::RSI
+            | Reg::RDI
+            | Reg::R8
+            | Reg::R9
+            | Reg::R10
+            | Reg::R11
+            | Reg::R12
+            | Reg::R13
+            | Reg::R14
+            | Reg::R15
+            | Reg::RIP
+            | Reg::CR0
+            | Reg::CR1
+            | Reg::CR2
+            | Reg::CR3
+            | Reg::CR4
+            | Reg::CR5
+            | Reg::CR6
+            | Reg::CR7
+            | Reg::CR8
+            | Reg::CR9
+            | Reg::CR10
+            | Reg::CR11
+            | Reg::CR12
+            | Reg::CR13
+            | Reg::CR14
+            | Reg::CR15
+            | Reg::DR0
+            | Reg::DR1
+            | Reg::DR2
+            | Reg::DR3
+            | Reg::DR4
+            | Reg::DR5
+            | Reg::DR6
+            | Reg::DR7
+            | Reg::DR8
+            | Reg::DR9
+            | Reg::DR10
+            | Reg::DR11
+            | Reg::DR12
+            | Reg::DR13
+            | Reg::DR14
+            | Reg::DR15
+            | Reg::MM0
+            | Reg::MM1
+            | Reg::MM2
+            | Reg::MM3
+            | Reg::MM4
+            | Reg::MM5
+            | Reg::MM6
+            | Reg::MM7
+            | Reg::K0
+            | Reg::K1
+            | Reg::K2
+            | Reg::K3
+            | Reg::K4
+            | Reg::K5
+            | Reg::K6
+            | Reg::K7 => 64,
+            Reg::ST0
+            | Reg::ST1
+            | Reg::ST2
+            | Reg::ST3
+            | Reg::ST4
+            | Reg::ST5
+            | Reg::ST6
+            | Reg::ST7 => 80,
+            Reg::XMM0
+            | Reg::XMM1
+            | Reg::XMM2
+            | Reg::XMM3
+            | Reg::XMM4
+            | Reg::XMM5
+            | Reg::XMM6
+            | Reg::XMM7
+            | Reg::XMM8
+            | Reg::XMM9
+            | Reg::XMM10
+            | Reg::XMM11
+            | Reg::XMM12
+            | Reg::XMM13
+            | Reg::XMM14
+            | Reg::XMM15
+            | Reg::XMM16
+            | Reg::XMM17
+            | Reg::XMM18
+            | Reg::XMM19
+            | Reg::XMM20
+            | Reg::XMM21
+            | Reg::XMM22
+            | Reg::XMM23
+            | Reg::XMM24
+            | Reg::XMM25
+            | Reg::XMM26
+            | Reg::XMM27
+            | Reg::XMM28
+            | Reg::XMM29
+            | Reg::XMM30
+            | Reg::XMM31 => 128,
+            Reg::YMM0
+            | Reg::YMM1
+            | Reg::YMM2
+            | Reg::YMM3
+            | Reg::YMM4
+            | Reg::YMM5
+            | Reg::YMM6
+            | Reg::YMM7
+            | Reg::YMM8
+            | Reg::YMM9
+            | Reg::YMM10
+            | Reg::YMM11
+            | Reg::YMM12
+            | Reg::YMM13
+            | Reg::YMM14
+            | Reg::YMM15
+            | Reg::YMM16
+            | Reg::YMM17
+            | Reg::YMM18
+            | Reg::YMM19
+            | Reg::YMM20
+            | Reg::YMM21
+            | Reg::YMM22
+            | Reg::YMM23
+            | Reg::YMM24
+            | Reg::YMM25
+            | Reg::YMM26
+            | Reg::YMM27
+            | Reg::YMM28
+            | Reg::YMM29
+            | Reg::YMM30
+            | Reg::YMM31 => 256,
+            Reg::ZMM0
+            | Reg::ZMM1
+            | Reg::ZMM2
+            | Reg::ZMM3
+            | Reg::ZMM4
+            | Reg::ZMM5
+            | Reg::ZMM6
+            | Reg::ZMM7
+            | Reg::ZMM8
+            | Reg::ZMM9
+            | Reg::ZMM10
+            | Reg::ZMM11
+            | Reg::ZMM12
+            | Reg::ZMM13
+            | Reg::ZMM14
+            | Reg::ZMM15
+            | Reg::ZMM16
+            | Reg::ZMM17
+            | Reg::ZMM18
+            | Reg::ZMM19
+            | Reg::ZMM20
+            | Reg::ZMM21
+            | Reg::ZMM22
+            | Reg::ZMM23
+            | Reg::ZMM24
+            | Reg::ZMM25
+            | Reg::ZMM26
+            | Reg::ZMM27
+            | Reg::ZMM28
+            | Reg::ZMM29
+            | Reg::ZMM30
+            | Reg::ZMM31 => 512,
+        }
+    }
+
+    /// Returns the offset in bits of the register within its `parent`, which is 8 for the high
+    /// byte registers and 0 for all the others
+    pub fn bit_offset(&self) -> u16 {
+        match self {
+            Reg::AH | Reg::CH | Reg::DH | Reg::BH => 8,
+            _ => 0,
         }
     }
+
+    /// Returns the full-width register that contains this register: the 64-bit register for a
+    /// general-purpose register, `rip` for the instruction pointer and the ZMM register for a
+    /// vector register. Any other register is its own parent.
+    pub fn parent(&self) -> Reg {
+        const ZMM: [Reg; 32] = [
+            Reg::ZMM0,
+            Reg::ZMM1,
+            Reg::ZMM2,
+            Reg::ZMM3,
+            Reg::ZMM4,
+            Reg::ZMM5,
+            Reg::ZMM6,
+            Reg::ZMM7,
+            Reg::ZMM8,
+            Reg::ZMM9,
+            Reg::ZMM10,
+            Reg::ZMM11,
+            Reg::ZMM12,
+            Reg::ZMM13,
+            Reg::ZMM14,
+            Reg::ZMM15,
+            Reg::ZMM16,
+            Reg::ZMM17,
+            Reg::ZMM18,
+            Reg::ZMM19,
+            Reg::ZMM20,
+            Reg::ZMM21,
+            Reg::ZMM22,
+            Reg::ZMM23,
+            Reg::ZMM24,
+            Reg::ZMM25,
+            Reg::ZMM26,
+            Reg::ZMM27,
+            Reg::ZMM28,
+            Reg::ZMM29,
+            Reg::ZMM30,
+            Reg::ZMM31,
+        ];
+        match self.class() {
+            RegClass::Gpr => Reg::from_rm64(self.index()),
+            RegClass::InstructionPointer => Reg::RIP,
+            RegClass::Xmm | RegClass::Ymm => ZMM[usize::from(self.index())],
+            _ => *self,
+        }
+    }
+
+    /// Returns `true` if the two registers share any bits, like `ah` and `eax`, but not `ah` and
+    /// `al`
+    pub fn overlaps(&self, other: &Reg) -> bool {
+        let start = self.bit_offset();
+        let other_start = other.bit_offset();
+        self.parent() == other.parent()
+            && start < other_start + other.width()
+            && other_start < start + self.width()
+    }
+
+    /// Returns the family of a general-purpose register, which holds all its widths
+    pub fn family(&self) -> Option<RegFamily> {
+        match self.class() {
+            RegClass::Gpr => Some(RegFamily::from(self.index())),
+            _ => None,
+        }
+    }
+
+    /// Returns the register encoded by the same number when the instruction has no REX prefix.
+    /// `spl`, `bpl`, `sil` and `dil` can only be encoded with a REX prefix, and without one their
+    /// numbers select `ah`, `ch`, `dh` and `bh`.
+    pub fn without_rex(self) -> Reg {
+        match self {
+            Reg::SPL => Reg::AH,
+            Reg::BPL => Reg::CH,
+            Reg::SIL => Reg::DH,
+            Reg::DIL => Reg::BH,
+            reg => reg,
+        }
+    }
+
+    /// Returns the register of the same family with the width of `op_size`. Registers which are
+    /// not general-purpose or the instruction pointer do not change with the operand size.
+    pub fn convert_with_opsize(self, op_size: &OpSize) -> Reg {
+        match (self.family(), self.class()) {
+            (Some(family), _) => family.reg_from(op_size),
+            (None, RegClass::InstructionPointer) => match op_size {
+                OpSize::U16 | OpSize::I16 => Reg::IP,
+                OpSize::U32 | OpSize::I32 => Reg::EIP,
+                _ => Reg::RIP,
+            },
+            (None, _) => self,
+        }
+    }
+
     // Convert the value to a register, specified by r/m16
     // A word general-purpose register or memory operand used for instructions whose operand-size
     // attribute is 16 bits. The word general-purpose registers are: AX, CX, DX, BX, SP, BP, SI,
@@ -2,6 +2,7 @@
 /// - Specify GPRs and SSE registers.
 /// - Specify 64-bit operand size.
 /// - Specifiy extended control registers.
+///
 /// This type of prefix is necessary only if an instruction references one of the extended
 /// registers or uses a 64-bit operand. If a REX prefix is used when it has no meaning, it is
 /// ignored.
@@ -0,0 +1,277 @@
+//! Module that writes a disassembly as source for an assembler, such that assembling it gives back
+//! the bytes it was decoded from. Branch targets get labels, and the bytes which cannot be decoded
+//! are written as data.
+use crate::{
+    arch::Arch,
+    constant::constant_names,
+    cpu::DecoderConfig,
+    dis::Decoded,
+    format::{write_immediate_comment, write_named, write_prefixes, FormatOptions, Formatter},
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::{AddrSize, OpSize, OpcodeType, OperandEncoding},
+    reg::{Reg, RegClass},
+    token::{FormatterOutput, TokenKind},
+};
+use core::fmt;
+use std::collections::BTreeSet;
+
+/// A `Formatter` whose output an assembler accepts
+pub trait Assembler: Formatter {
+    /// Writes the directives the source starts with, which select the code width of `config`
+    fn write_header(&self, out: &mut dyn FormatterOutput, config: &DecoderConfig) -> fmt::Result;
+
+    /// Writes the directives the source ends with
+    fn write_footer(&self, _out: &mut dyn FormatterOutput) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Writes bytes which could not be decoded as an instruction, as data
+    fn write_bytes(&self, out: &mut dyn FormatterOutput, bytes: &[u8]) -> fmt::Result;
+
+    /// Returns whether the assembler encodes `inst` with other bytes, whatever keywords its
+    /// operands carry. Such an instruction is written as data, followed by the instruction in a
+    /// comment. Defaults to `has_shorter_form`.
+    fn reencodes(&self, inst: &Instruction) -> bool {
+        has_shorter_form(inst)
+    }
+}
+
+/// Returns whether `inst` has a shorter encoding with the same operands, which assemblers pick
+/// over the decoded one:
+/// - the `mov` of an immediate into a register by `c6 /0` or `c7 /0`, encoded as `b0+r` or `b8+r`
+/// - an immediate operation on the accumulator by `80`, `81`, `f6` or `f7`, encoded as `04`,
+///   `05`, `a8` or `a9` and the likes, unless the immediate is a byte extended to a larger operand
+/// - outside of 64-bit mode, a `mov` between the accumulator and an address without registers by
+///   ModRM, encoded as `a0`-`a3`
+/// - in 64-bit mode, a `mov` between the accumulator and a 32-bit address by `a0`-`a3`, where the
+///   address is encoded by ModRM
+pub fn has_shorter_form(inst: &Instruction) -> bool {
+    let is_accumulator = |operand: Option<&InstOperand>| {
+        matches!(
+            operand.map(|operand| operand.operand),
+            Some(ResolvedOperand::Reg(
+                Reg::AL | Reg::AX | Reg::EAX | Reg::RAX
+            ))
+        )
+    };
+    let is_absolute = |operand: Option<&InstOperand>| match operand.map(|operand| operand.operand) {
+        Some(ResolvedOperand::Mem((EffAddrType::None, maybe_sib, Some(_)))) => {
+            !maybe_sib.is_some_and(|sib| sib.base().is_some() || sib.index().is_some())
+        }
+        _ => false,
+    };
+    let mut operands = inst.operands.explicit();
+    let (first, second) = (operands.next(), operands.next());
+    match (inst.opcode.ident, inst.opcode.encoding) {
+        (OpcodeType::Mov, Some(OperandEncoding::MI)) => {
+            matches!(first.map(|op| op.operand), Some(ResolvedOperand::Reg(_)))
+        }
+        (
+            OpcodeType::Add
+            | OpcodeType::Or
+            | OpcodeType::Adc
+            | OpcodeType::Sbb
+            | OpcodeType::And
+            | OpcodeType::Sub
+            | OpcodeType::Xor
+            | OpcodeType::Cmp
+            | OpcodeType::Test,
+            Some(OperandEncoding::MI),
+        ) => {
+            let byte_extended = second.is_some_and(|operand| match operand.operand {
+                ResolvedOperand::Immediate(imm) => {
+                    imm.encoded_size() == OpSize::U8 && operand.size != OpSize::U8
+                }
+                _ => false,
+            });
+            is_accumulator(first) && !byte_extended
+        }
+        (OpcodeType::Mov, Some(OperandEncoding::MR | OperandEncoding::RM)) => {
+            inst.cpu_mode() != Arch::Arch64
+                && ((is_accumulator(first) && is_absolute(second))
+                    || (is_absolute(first) && is_accumulator(second)))
+        }
+        (OpcodeType::Mov, Some(OperandEncoding::FD | OperandEncoding::TD)) => {
+            inst.cpu_mode() == Arch::Arch64 && inst.address_size() != AddrSize::Addr64Bit
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether the immediate of `inst` could be encoded as a byte sign-extended to its operand,
+/// like the `83 /0` form of `add`, rather than with the size of its operand
+pub fn has_imm8_form(inst: &Instruction) -> bool {
+    !matches!(
+        inst.opcode.ident,
+        OpcodeType::Mov | OpcodeType::Test | OpcodeType::RetNear | OpcodeType::Enter
+    )
+}
+
+/// Returns the name of the label of the item at `offset`
+pub fn label(offset: usize) -> String {
+    format!("loc_{offset:x}")
+}
+
+/// Writes the items `decoded` from a buffer as source for `assembler`, one item per line. Each
+/// item which is the target of a branch or of a rip-relative operand gets a label, while the
+/// targets outside of the buffer are written relative to the instruction. Consecutive bytes which
+/// could not be decoded are written together as data, and so are the instructions the assembler
+/// would encode differently.
+pub fn write_source<A: Assembler + ?Sized>(
+    out: &mut dyn FormatterOutput,
+    assembler: &A,
+    decoded: &[Decoded],
+    config: &DecoderConfig,
+) -> fmt::Result {
+    let offsets: BTreeSet<usize> = decoded.iter().map(Decoded::offset).collect();
+    // Returns the offset `operand` refers to, if it is the offset of an item
+    let target = |inst: &Instruction, offset: usize, operand: &InstOperand| {
+        let target = inst.target(&operand.operand, offset as u64)?;
+        let target = usize::try_from(target).ok()?;
+        offsets.contains(&target).then_some(target)
+    };
+
+    let labels: BTreeSet<usize> = decoded
+        .iter()
+        .filter_map(|item| match item {
+            Decoded::Inst { offset, inst, .. } => Some((*offset, inst)),
+            Decoded::Data { .. } => None,
+        })
+        .flat_map(|(offset, inst)| {
+            inst.operands
+                .explicit()
+                .filter_map(move |operand| target(inst, offset, operand))
+        })
+        .collect();
+
+    // The names of the immediates whose use is known are only needed for their comments
+    let names = match assembler.options().immediate_comments() {
+        true => constant_names(decoded),
+        false => Default::default(),
+    };
+
+    assembler.write_header(out, config)?;
+    let mut data = vec![];
+    for item in decoded {
+        let offset = item.offset();
+        let has_label = labels.contains(&offset);
+        // Bytes are written as soon as an instruction or a label comes after them
+        let is_data = matches!(item, Decoded::Data { .. });
+        if !data.is_empty() && (has_label || !is_data) {
+            write!(out, "    ")?;
+            assembler.write_bytes(out, &data)?;
+            writeln!(out)?;
+            data.clear();
+        }
+        if has_label {
+            out.write_token(TokenKind::Symbol, &label(offset))?;
+            writeln!(out, ":")?;
+        }
+
+        match item {
+            Decoded::Inst { inst, bytes, .. } => {
+                write!(out, "    ")?;
+                let name_target = |operand: &InstOperand| target(inst, offset, operand).map(label);
+                match assembler.reencodes(inst) {
+                    true => {
+                        assembler.write_bytes(out, bytes)?;
+                        let mut text = String::new();
+                        write_named(&mut text, assembler, inst, name_target)?;
+                        assembler.write_comment(out, &text)?;
+                    }
+                    false => write_named(out, assembler, inst, name_target)?,
+                }
+                let name = names.get(&offset).map(String::as_str);
+                write_immediate_comment(out, assembler, inst, name)?;
+                writeln!(out)?;
+            }
+            Decoded::Data { byte, .. } => data.push(*byte),
+        }
+    }
+    if !data.is_empty() {
+        write!(out, "    ")?;
+        assembler.write_bytes(out, &data)?;
+        writeln!(out)?;
+    }
+    assembler.write_footer(out)
+}
+
+/// Writes the mnemonic of `inst` the way assemblers expect it, preceded by its lock and repeat
+/// prefixes. String instructions take the size of their operands as a suffix instead of operands,
+/// like `movsb` or `stosq`.
+pub fn write_source_mnemonic(
+    out: &mut dyn FormatterOutput,
+    options: &FormatOptions,
+    inst: &Instruction,
+) -> fmt::Result {
+    write_prefixes(out, options, inst)?;
+    options.write_name(out, TokenKind::Mnemonic, &string_mnemonic(inst))
+}
+
+/// Returns the mnemonic of `inst`, followed by the size of its operands if it is a string
+/// instruction, like `movsb` or `stosq`
+pub fn string_mnemonic(inst: &Instruction) -> String {
+    let mut mnemonic = String::from(inst.opcode.mnemonic);
+    if inst.opcode.ident.is_string() {
+        let size = inst
+            .operands
+            .explicit()
+            .find_map(|operand| match operand.operand {
+                ResolvedOperand::Mem(_) => Some(operand.size),
+                _ => None,
+            });
+        match size {
+            Some(OpSize::U8 | OpSize::I8) => mnemonic.push('b'),
+            Some(OpSize::U16 | OpSize::I16) => mnemonic.push('w'),
+            Some(OpSize::U32 | OpSize::I32) => mnemonic.push('d'),
+            Some(OpSize::U64 | OpSize::I64) => mnemonic.push('q'),
+            _ => {}
+        }
+    }
+    mnemonic
+}
+
+/// Returns the operands assemblers expect for `inst`, which are none for string instructions
+pub fn source_operands(inst: &Instruction) -> Vec<&InstOperand> {
+    match inst.opcode.ident.is_string() {
+        true => vec![],
+        false => inst.operands.explicit().collect(),
+    }
+}
+
+/// Returns whether the memory `operand` is relative to the instruction pointer
+pub fn is_rip_relative(operand: &ResolvedOperand) -> bool {
+    matches!(
+        operand,
+        ResolvedOperand::Mem((EffAddrType::Reg(reg), _, _))
+            if reg.class() == RegClass::InstructionPointer
+    )
+}
+
+/// Returns the offset `operand` refers to from the start of `inst`, if it is a relative branch
+/// target or a rip-relative memory operand. Assemblers write it `$+offset`.
+pub fn relative_offset(inst: &Instruction, operand: &ResolvedOperand) -> Option<i64> {
+    // The offsets are relative to the next instruction
+    let len = inst.layout().len() as i64;
+    match operand {
+        ResolvedOperand::Relative(rel) => Some(len + rel.signed_value()),
+        ResolvedOperand::Mem((_, _, maybe_disp)) if is_rip_relative(operand) => {
+            Some(len + maybe_disp.map_or(0, |disp| disp.value()))
+        }
+        _ => None,
+    }
+}
+
+/// Writes `$+offset`, the address at `offset` from the start of the instruction
+pub fn write_relative(
+    out: &mut dyn FormatterOutput,
+    options: &FormatOptions,
+    offset: i64,
+) -> fmt::Result {
+    out.write_token(TokenKind::Symbol, "$")?;
+    if offset >= 0 {
+        write!(out, "+")?;
+    }
+    options.write_signed_hex(out, TokenKind::Immediate, offset)
+}
@@ -0,0 +1,145 @@
+//! Module that decodes instructions from bytes which arrive in chunks, like JIT code or packet
+//! payloads read from a pipe or a socket
+use crate::{
+    cpu::DecoderConfig,
+    dis::{Decoded, Recovery},
+    error::{DecodeError, DecodeErrorKind},
+    inst::{Instruction, Prefixes},
+    reader::Reader,
+};
+
+/// The result of asking the `StreamDecoder` for the next item
+#[derive(Debug)]
+pub enum Poll {
+    // The next instruction, or data byte if we recover from errors, is ready
+    Ready(Box<Decoded>),
+    // The next instruction needs at least this many more bytes to be fed, before it can be decoded
+    NeedMoreBytes(usize),
+    // The stream was closed and all its bytes were decoded
+    Finished,
+}
+
+/// Decodes a stream of bytes that are fed in chunks. An instruction which is split between 2
+/// chunks is reported with `Poll::NeedMoreBytes` instead of an error, and its decoding resumes
+/// once the next chunk is fed, without reading its prefixes again.
+pub struct StreamDecoder {
+    config: DecoderConfig,
+    recovery: Recovery,
+    // Holds the bytes fed and not decoded yet
+    reader: Reader,
+    // Offset in the stream of the first byte in `reader`
+    base: usize,
+    // The position in `reader` and the prefixes of an instruction that is still missing bytes
+    pending: Option<(usize, Prefixes)>,
+    // Whether there are no more bytes to be fed
+    closed: bool,
+}
+
+impl StreamDecoder {
+    pub fn new(config: DecoderConfig) -> Self {
+        Self {
+            config,
+            recovery: Recovery::Abort,
+            reader: Reader::from_vec(vec![]),
+            base: 0,
+            pending: None,
+            closed: false,
+        }
+    }
+
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Appends the next chunk of the stream
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.reader.extend(chunk);
+    }
+
+    /// Marks the end of the stream. From now on, an instruction that is missing bytes is reported
+    /// as an error, or as data if we recover from errors.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// Returns the offset in the stream of the next item that will be decoded
+    pub fn offset(&self) -> usize {
+        match &self.pending {
+            Some((inst_pos, _)) => self.base + inst_pos,
+            None => self.base + self.reader.pos(),
+        }
+    }
+
+    /// Decodes the next item from the bytes fed until now
+    pub fn poll(&mut self) -> Result<Poll, DecodeError> {
+        // Once nothing refers to the bytes we already decoded, we can drop them
+        if self.pending.is_none() {
+            self.base += self.reader.drop_read();
+
+            if self.reader.bytes_unread() == 0 {
+                return Ok(match self.closed {
+                    true => Poll::Finished,
+                    false => Poll::NeedMoreBytes(1),
+                });
+            }
+        }
+
+        let (inst_pos, mut prefixes) = self
+            .pending
+            .take()
+            .unwrap_or_else(|| (self.reader.pos(), Prefixes::default()));
+
+        let result = Instruction::from_reader_with_prefixes(
+            &mut self.reader,
+            inst_pos,
+            &self.config,
+            &mut prefixes,
+        );
+
+        // Errors report offsets in the stream, and not in our buffer
+        let err = match result {
+            Ok(inst) => {
+                return Ok(Poll::Ready(Box::new(Decoded::Inst {
+                    offset: self.base + inst_pos,
+                    bytes: self.reader.bytes_since(inst_pos).to_vec(),
+                    inst: Box::new(inst),
+                })))
+            }
+            Err(err) => DecodeError::new(
+                self.base + err.inst_offset(),
+                self.base + err.offset(),
+                err.bytes(),
+                err.component(),
+                err.kind().clone(),
+            ),
+        };
+
+        if let DecodeErrorKind::NotEnoughBytes(needed) = err.kind() {
+            if !self.closed {
+                // Go back to right after the prefixes, which we keep, such that we resume from
+                // the opcode once we are fed more bytes
+                self.reader
+                    .seek(inst_pos + prefixes.len())
+                    .map_err(|_| err.clone())?;
+                self.pending = Some((inst_pos, prefixes));
+                return Ok(Poll::NeedMoreBytes(*needed));
+            }
+        }
+
+        // Go back to the start of the failing instruction, such that the next call decodes from
+        // there, or from the next byte if we recover from the error
+        self.reader.seek(inst_pos).map_err(|_| err.clone())?;
+        match self.recovery {
+            Recovery::Abort => Err(err),
+            Recovery::SkipByte => {
+                let byte = self.reader.read::<u8>().map_err(|_| err.clone())?;
+                Ok(Poll::Ready(Box::new(Decoded::Data {
+                    offset: self.base + inst_pos,
+                    byte,
+                    error: err,
+                })))
+            }
+        }
+    }
+}
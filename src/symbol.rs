@@ -0,0 +1,113 @@
+//! Module that names the addresses instructions refer to, such that listings read `call <main+0x10>`
+//! instead of `call 0x4b20`. A `Formatter` asks a `Symbolizer` for the name of every absolute
+//! address it writes: branch targets, rip-relative and absolute memory operands, and immediates of
+//! 32 bits or more, which are often addresses.
+use crate::{
+    inst::{InstOperand, Instruction, ResolvedOperand},
+    modrm::EffAddrType,
+    opcode::OpSize,
+};
+
+/// Names addresses, usually after the symbols of an object file
+pub trait Symbolizer {
+    /// Returns the name of `address`, like `main` or `main+0x10`, or `None` if it has none
+    fn symbolize(&self, address: u64) -> Option<String>;
+}
+
+impl<F: Fn(u64) -> Option<String>> Symbolizer for F {
+    fn symbolize(&self, address: u64) -> Option<String> {
+        self(address)
+    }
+}
+
+// A symbol of a `SymbolTable`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TableSymbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+impl TableSymbol {
+    // Returns whether `address` is inside the symbol. A symbol without a size only names its own
+    // address.
+    fn contains(&self, address: u64) -> bool {
+        address == self.address || address - self.address < self.size
+    }
+}
+
+/// A `Symbolizer` backed by a table of symbols sorted by address. An address inside a symbol is
+/// named after it, with the offset from its start, like `main+0x10`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: Vec<TableSymbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the symbol `name`, which takes `size` bytes from `address`. Of the symbols which start
+    /// at the same address, the first one added names it.
+    pub fn with_symbol(mut self, address: u64, size: u64, name: &str) -> Self {
+        let idx = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        self.symbols.insert(
+            idx,
+            TableSymbol {
+                address,
+                size,
+                name: name.to_string(),
+            },
+        );
+        self
+    }
+}
+
+impl Symbolizer for SymbolTable {
+    fn symbolize(&self, address: u64) -> Option<String> {
+        // Only the symbols which start the closest to the address can contain it
+        let end = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        let closest = self.symbols[..end].last()?.address;
+        let symbol = self.symbols[..end]
+            .iter()
+            .rev()
+            .take_while(|symbol| symbol.address == closest)
+            .filter(|symbol| symbol.contains(address))
+            .last()?;
+        match address - symbol.address {
+            0 => Some(symbol.name.clone()),
+            offset => Some(format!("{}+0x{offset:x}", symbol.name)),
+        }
+    }
+}
+
+/// Returns the absolute address `operand` of `inst` refers to, if `inst` is decoded at `address`.
+/// Relative branches and rip-relative operands refer to an address relative to the instruction,
+/// memory operands without registers to their displacement, and immediates of 32 bits or more to
+/// their value.
+pub fn operand_address(inst: &Instruction, operand: &InstOperand, address: u64) -> Option<u64> {
+    match operand.operand {
+        ResolvedOperand::Relative(_) => inst.target(&operand.operand, address),
+        ResolvedOperand::Mem((eff_addr, maybe_sib, maybe_disp)) => match eff_addr {
+            EffAddrType::Reg(_) => inst.target(&operand.operand, address),
+            EffAddrType::Sib | EffAddrType::None => {
+                let has_reg =
+                    maybe_sib.is_some_and(|sib| sib.base().is_some() || sib.index().is_some());
+                match has_reg {
+                    true => None,
+                    false => maybe_disp.map(|disp| disp.unsigned()),
+                }
+            }
+        },
+        ResolvedOperand::Immediate(imm) => match imm.encoded_size() {
+            OpSize::U32 | OpSize::I32 | OpSize::U64 | OpSize::I64 => Some(imm.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,192 @@
+//! Module that describes the opcode table. The table itself is generated by `build.rs` from
+//! `opcodes.tbl`, such that adding an instruction only needs a new line in that file.
+use crate::{
+    arch::Arch,
+    cond::Condition,
+    cpu::Extension,
+    opcode::{Access, AddressingMethod, OpcodeType, Operand, OperandEncoding, OperandType},
+    prefix::{Group1, Prefix},
+    reg::{Reg, RegFamily, SegmentRegister},
+};
+use core::fmt;
+
+/// The opcode maps an opcode byte can belong to. The map is selected by the escape bytes in front
+/// of the opcode, or by the VEX/EVEX prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeMap {
+    OneByte,
+    Map0F,
+    Map0F38,
+    Map0F3A,
+    Vex0F,
+    Vex0F38,
+    Vex0F3A,
+    Evex0F,
+    Evex0F38,
+    Evex0F3A,
+}
+
+impl OpcodeMap {
+    /// Returns the escape bytes that select the map, for the maps that are selected by them
+    pub fn escape(&self) -> &'static [u8] {
+        match self {
+            Self::OneByte => &[],
+            Self::Map0F => &[0x0F],
+            Self::Map0F38 => &[0x0F, 0x38],
+            Self::Map0F3A => &[0x0F, 0x3A],
+            _ => &[],
+        }
+    }
+}
+
+/// The prefix which is part of the opcode, typically for SSE instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MandatoryPrefix {
+    None,
+    P66,
+    PF2,
+    PF3,
+}
+
+impl MandatoryPrefix {
+    /// Returns the prefix from `prefixs` that can be a mandatory prefix. A 0xF2 or 0xF3 prefix
+    /// takes precedence over 0x66, and the last one wins if both 0xF2 and 0xF3 are present.
+    pub fn from_prefixes(prefixs: &[Prefix]) -> Self {
+        let rep = prefixs.iter().rev().find_map(|prefix| match prefix {
+            Prefix::Group1(Group1::RepNE) => Some(Self::PF2),
+            Prefix::Group1(Group1::Rep) => Some(Self::PF3),
+            _ => None,
+        });
+        match rep {
+            Some(prefix) => prefix,
+            None if prefixs.contains(&Prefix::OpSize) => Self::P66,
+            None => Self::None,
+        }
+    }
+}
+
+/// Specifies how the ModRM byte takes part in selecting the instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModRMSelector {
+    // The ModRM byte does not select the instruction
+    None,
+    // The ModRM.reg field extends the opcode of a group
+    Reg(u8),
+    // The whole ModRM byte is part of the opcode
+    Byte(u8),
+}
+
+/// The operating modes an instruction is valid in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modes {
+    All,
+    // Invalid in 64-bit mode
+    Invalid64,
+    // Only valid in 64-bit mode
+    Only64,
+}
+
+impl Modes {
+    pub fn valid_in(&self, arch: Arch) -> bool {
+        match self {
+            Self::All => true,
+            Self::Invalid64 => arch != Arch::Arch64,
+            Self::Only64 => arch == Arch::Arch64,
+        }
+    }
+}
+
+/// An operand, as written in the opcode table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandSpec {
+    // An operand given by its addressing method and operand type, like `Ev`
+    Map(AddressingMethod, OperandType),
+    // A register encoded in the lower 3 bits of the opcode
+    RegInOpcode,
+    Reg(Reg),
+    RegFamily(RegFamily),
+    Segment(SegmentRegister),
+}
+
+impl OperandSpec {
+    /// Converts the specification to the operand of the instruction with the opcode byte `opcode`
+    pub fn to_operand(self, opcode: u8, arch: Arch) -> Operand {
+        match self {
+            Self::Map(addr_meth, op_type) => Operand::from_map(addr_meth, op_type, arch),
+            Self::RegInOpcode => Operand::RegInOpcode(opcode),
+            Self::Reg(reg) => Operand::Reg(reg),
+            Self::RegFamily(family) => Operand::RegFamily(family),
+            Self::Segment(seg_reg) => Operand::Segment(seg_reg),
+        }
+    }
+}
+
+impl fmt::Display for OperandSpec {
+    // Writes the operand code used by the opcode table and the Intel opcode maps, like `Ev`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Map(AddressingMethod::M, OperandType::V) => write!(f, "M"),
+            Self::Map(addr_meth, op_type) => {
+                write!(f, "{addr_meth:?}{}", format!("{op_type:?}").to_lowercase())
+            }
+            Self::RegInOpcode => write!(f, "Zv"),
+            Self::Reg(reg) => write!(f, "{}", reg.to_string().to_uppercase()),
+            Self::RegFamily(family) => {
+                let name = match family {
+                    RegFamily::Accumulator => "rAX",
+                    RegFamily::Counter => "rCX",
+                    RegFamily::Data => "rDX",
+                    RegFamily::Base => "rBX",
+                    RegFamily::StackPointer => "rSP",
+                    RegFamily::BasePointer => "rBP",
+                    RegFamily::Source => "rSI",
+                    RegFamily::Destination => "rDI",
+                    family => return write!(f, "{family:?}"),
+                };
+                write!(f, "{name}")
+            }
+            Self::Segment(seg_reg) => write!(f, "{}", seg_reg.to_string().to_uppercase()),
+        }
+    }
+}
+
+/// One instruction form of the opcode table
+#[derive(Debug)]
+pub struct OpcodeEntry {
+    pub map: OpcodeMap,
+    pub opcode: u8,
+    pub prefix: MandatoryPrefix,
+    pub modrm: ModRMSelector,
+    pub mnemonic: &'static str,
+    pub ident: OpcodeType,
+    pub operands: [Option<(OperandSpec, Access)>; 4],
+    // Registers the instruction accesses without encoding them
+    pub implicit: [Option<(OperandSpec, Access)>; 4],
+    pub encoding: OperandEncoding,
+    pub modes: Modes,
+    pub extension: Option<Extension>,
+}
+
+// Defines `OPCODE_TABLE`, which holds all the entries sorted by map, opcode, mandatory prefix and
+// ModRM selector, and `OPCODE_INDEX`, which holds the start and the number of entries of each
+// opcode, for each map
+include!(concat!(env!("OUT_DIR"), "/opcode_table.rs"));
+
+/// Returns the forms of the instruction with `opcode` in `map` which use the `prefix` mandatory
+/// prefix. If there are none, the prefix is just a legacy prefix and we return the forms without
+/// a mandatory prefix.
+pub fn lookup(map: OpcodeMap, opcode: u8, prefix: MandatoryPrefix) -> &'static [OpcodeEntry] {
+    let (start, count) = OPCODE_INDEX[map as usize][opcode as usize];
+    let entries = &OPCODE_TABLE[start as usize..(start + count) as usize];
+
+    let with_prefix = |prefix| {
+        let start = entries.partition_point(|entry| (entry.prefix as u8) < prefix as u8);
+        let end = entries.partition_point(|entry| entry.prefix as u8 <= prefix as u8);
+        &entries[start..end]
+    };
+
+    match with_prefix(prefix) {
+        [] => with_prefix(MandatoryPrefix::None),
+        forms => forms,
+    }
+}
@@ -0,0 +1,158 @@
+//! Module that splits formatted instructions into typed tokens, such that viewers can highlight
+//! them without parsing the text again. A `Formatter` writes each piece of an instruction as a
+//! token of some kind, which plain text outputs ignore and a `Tokens` collector records.
+use core::fmt;
+
+/// The kind of a piece of a formatted instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    // A lock or repeat prefix, like `rep`
+    Prefix,
+    Mnemonic,
+    Register,
+    Immediate,
+    // The brackets or the parentheses around a memory address
+    MemoryBracket,
+    // The displacement of a memory address
+    Displacement,
+    // A label or a symbol which names an address, like `loc_10` or `<main+0x10>`
+    Symbol,
+    // A keyword which qualifies an operand, like `qword ptr` or `short`
+    Keyword,
+    // A comment which follows the instruction, like `; 47 '/'`
+    Comment,
+    // Commas, operators and the other separators
+    Punctuation,
+    Whitespace,
+}
+
+/// A piece of a formatted instruction, together with its kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Where a `Formatter` writes instructions. Plain text outputs ignore the kinds of the tokens,
+/// while `Tokens` records them.
+pub trait FormatterOutput: fmt::Write {
+    /// Writes `text`, which is a token of `kind`
+    fn write_token(&mut self, _kind: TokenKind, text: &str) -> fmt::Result {
+        self.write_str(text)
+    }
+}
+
+impl FormatterOutput for String {}
+
+impl FormatterOutput for fmt::Formatter<'_> {}
+
+/// Collects the tokens a `Formatter` writes. Text written without a kind is whitespace or
+/// punctuation, and consecutive pieces of the same kind are merged into one token.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tokens {
+    tokens: Vec<Token>,
+}
+
+impl Tokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the tokens written until now
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Returns the number of characters written until now
+    pub fn width(&self) -> usize {
+        self.tokens
+            .iter()
+            .map(|token| token.text.chars().count())
+            .sum()
+    }
+
+    /// Returns the tokens written until now, consuming the collector
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+
+    // Appends `text` of `kind` to the last token if it has the same kind, or as a new token
+    fn push(&mut self, kind: TokenKind, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.tokens.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => self.tokens.push(Token::new(kind, text)),
+        }
+    }
+}
+
+impl fmt::Write for Tokens {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        // Untyped text is split on whitespace, which separates the punctuation from the rest
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let is_space = c.is_whitespace();
+            let end = rest
+                .find(|c: char| c.is_whitespace() != is_space)
+                .unwrap_or(rest.len());
+            let kind = match is_space {
+                true => TokenKind::Whitespace,
+                false => TokenKind::Punctuation,
+            };
+            self.push(kind, &rest[..end]);
+            rest = &rest[end..];
+        }
+        Ok(())
+    }
+}
+
+impl FormatterOutput for Tokens {
+    fn write_token(&mut self, kind: TokenKind, text: &str) -> fmt::Result {
+        // The spaces around a keyword, like `short `, are not part of it
+        let start = text.len() - text.trim_start().len();
+        let end = text.trim_end().len().max(start);
+        self.push(TokenKind::Whitespace, &text[..start]);
+        self.push(kind, &text[start..end]);
+        self.push(TokenKind::Whitespace, &text[end..]);
+        Ok(())
+    }
+}
+
+/// Returns the ANSI escape sequence which selects the color of the tokens of `kind`, or `None`
+/// for the tokens which are written in the default color
+pub fn ansi_color(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::Prefix => Some("\x1b[35m"),
+        TokenKind::Mnemonic => Some("\x1b[1;33m"),
+        TokenKind::Register => Some("\x1b[36m"),
+        TokenKind::Immediate => Some("\x1b[32m"),
+        TokenKind::MemoryBracket => Some("\x1b[1m"),
+        TokenKind::Displacement => Some("\x1b[32m"),
+        TokenKind::Symbol => Some("\x1b[34m"),
+        TokenKind::Keyword => Some("\x1b[2m"),
+        TokenKind::Comment => Some("\x1b[90m"),
+        TokenKind::Punctuation | TokenKind::Whitespace => None,
+    }
+}
+
+/// Writes `tokens` for a terminal, colored with ANSI escape sequences
+pub fn write_ansi(out: &mut dyn fmt::Write, tokens: &[Token]) -> fmt::Result {
+    for token in tokens {
+        match ansi_color(token.kind) {
+            Some(color) => write!(out, "{color}{}\x1b[0m", token.text)?,
+            None => write!(out, "{}", token.text)?,
+        }
+    }
+    Ok(())
+}